@@ -55,6 +55,85 @@ fn main() {
         f.write_all("  ],\n".as_bytes()).unwrap();
     }
     f.write_all("];\n".as_bytes()).unwrap();
+
+    // Write the inverse matrices for per-pixel enumeration.
+    let matrices = generate_pixel_inverse_matrices(&vectors[1]);
+    f.write_all(
+        format!(
+            "pub const PIXEL_INVERSE_MATRICES: &[[u32; {0}]; {0}] = &[\n",
+            MAX_PIXEL_BITS
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    for m in matrices.iter() {
+        f.write_all("  [\n".as_bytes()).unwrap();
+        for n in m.iter() {
+            f.write_all(format!("    0x{:04x},\n", *n).as_bytes())
+                .unwrap();
+        }
+        f.write_all("  ],\n".as_bytes()).unwrap();
+    }
+    f.write_all("];\n".as_bytes()).unwrap();
+}
+
+//======================================================================
+// Inverse matrices for enumerating the Sobol indices within a pixel, as in
+// "Enumerating Quasi-Monte Carlo Point Sequences in Elementary Intervals"
+// by Leonhard Gruenschloss, Matthias Raab, and Alexander Keller.
+
+/// The largest pixel grid is 2^MAX_PIXEL_BITS x 2^MAX_PIXEL_BITS.
+const MAX_PIXEL_BITS: usize = SOBOL_BITS / 2;
+
+/// For a 2^k x 2^k pixel grid over dimensions 0 and 1, index bits [0, k)
+/// are fixed by the pixel's x coordinate, since dimension 0 is the van der
+/// Corput sequence.  Index bits [k, 2k) then have to be solved for from the
+/// pixel's y coordinate, which is a k x k linear system over GF(2) with
+/// matrix `M[row][col] = bit (31 - row) of dim_1[k + col]`.
+///
+/// This computes the inverse of that matrix for each k in [1, MAX_PIXEL_BITS].
+/// Entry `[k - 1][row]` is the bitmask of unknown index bits that the given
+/// row (bit of the y coordinate, from the most significant) contributes to.
+fn generate_pixel_inverse_matrices(dim_1: &[SobolInt; SOBOL_BITS]) -> Vec<[u32; MAX_PIXEL_BITS]> {
+    let mut matrices = Vec::new();
+    for k in 1..=MAX_PIXEL_BITS {
+        // Augmented matrix rows: (matrix row bits, identity row bits).
+        let mut rows: Vec<(u32, u32)> = (0..k)
+            .map(|row| {
+                let mut bits = 0;
+                for col in 0..k {
+                    bits |= ((dim_1[k + col] >> (SOBOL_BITS - 1 - row)) & 1) << col;
+                }
+                (bits, 1 << row)
+            })
+            .collect();
+
+        // Gauss-Jordan elimination.
+        for col in 0..k {
+            let pivot = (col..k)
+                .find(|&r| (rows[r].0 >> col) & 1 != 0)
+                .expect("Sobol pixel matrix is singular.");
+            rows.swap(col, pivot);
+            for r in 0..k {
+                if r != col && (rows[r].0 >> col) & 1 != 0 {
+                    rows[r].0 ^= rows[col].0;
+                    rows[r].1 ^= rows[col].1;
+                }
+            }
+        }
+
+        // Row `col` of the inverse now says which of the right-hand side
+        // rows sum to unknown `col`.  Transpose that into per-row masks.
+        let mut matrix = [0u32; MAX_PIXEL_BITS];
+        for (col, &(_, inv_row)) in rows.iter().enumerate() {
+            for (row, mask) in matrix.iter_mut().enumerate().take(k) {
+                *mask |= ((inv_row >> row) & 1) << col;
+            }
+        }
+        matrices.push(matrix);
+    }
+
+    matrices
 }
 
 //======================================================================
@@ -114,8 +193,8 @@ pub fn generate_direction_vectors(dimensions: usize) -> Vec<[SobolInt; SOBOL_BIT
 
     // Calculate first dimension, which is just the van der Corput sequence.
     let mut dim_0 = [0 as SobolInt; SOBOL_BITS];
-    for i in 0..SOBOL_BITS {
-        dim_0[i] = 1 << (SOBOL_BITS - 1 - i);
+    for (i, v) in dim_0.iter_mut().enumerate() {
+        *v = 1 << (SOBOL_BITS - 1 - i);
     }
     vectors.push(dim_0);
 
@@ -137,20 +216,20 @@ pub fn generate_direction_vectors(dimensions: usize) -> Vec<[SobolInt; SOBOL_BIT
         };
 
         // Generate the direction numbers for this dimension.
-        if SOBOL_BITS <= s as usize {
+        if SOBOL_BITS <= s {
             for i in 0..SOBOL_BITS {
                 v[i] = (m[i] << (SOBOL_BITS - 1 - i)) as SobolInt;
             }
         } else {
-            for i in 0..(s as usize) {
+            for i in 0..s {
                 v[i] = (m[i] << (SOBOL_BITS - 1 - i)) as SobolInt;
             }
 
-            for i in (s as usize)..SOBOL_BITS {
-                v[i] = v[i - s as usize] ^ (v[i - s as usize] >> s);
+            for i in s..SOBOL_BITS {
+                v[i] = v[i - s] ^ (v[i - s] >> s);
 
                 for k in 1..s {
-                    v[i] ^= ((a >> (s - 1 - k)) & 1) as SobolInt * v[i - k as usize];
+                    v[i] ^= ((a >> (s - 1 - k)) & 1) as SobolInt * v[i - k];
                 }
             }
         }
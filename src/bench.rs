@@ -0,0 +1,251 @@
+//! Simple timing of the Sobol sampler and the scrambling hashes.
+//!
+//! Everything is timed in two forms:
+//!
+//! - Scalar: each call depends on the result of the previous one, which
+//!   measures latency.
+//! - Batched: a buffer of independent inputs is processed at once, which
+//!   measures throughput, and is closer to how a renderer generating many
+//!   samples at once would use them.
+//!
+//! The batch APIs (e.g. `sobol::sobol_u32_batch()`) are also timed against
+//! their scalar versions, at the small batch sizes a renderer would use.
+//!
+//! Results are in nanoseconds per call (or per value, for batches).
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::hash_gen::{exec_hash_slice, exec_hash_slice_batch};
+use crate::scramble::{LkHash, OwenScrambler, Reference};
+use crate::sobol::{
+    owen_scramble_fast_u32, owen_scramble_fast_u32_batch, sobol_u32, sobol_u32_batch,
+    u32_to_0_1_f32, u32_to_0_1_f32_batch,
+};
+
+/// Number of independent inputs per batch in the batched timings.
+pub const BATCH_SIZE: usize = 1024;
+
+/// Sizes of the batches that the batch APIs are timed at.
+pub const API_BATCH_SIZES: &[usize] = &[8, 16];
+
+/// The timing results of one benchmark.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: String,
+    pub scalar_ns: f64,
+    pub batched_ns: f64,
+}
+
+/// The timing results of one batch API against its scalar version.
+#[derive(Debug, Clone)]
+pub struct BatchBenchResult {
+    pub name: String,
+    pub batch_size: usize,
+    pub scalar_ns: f64, // The scalar version called on each value of a batch.
+    pub batch_ns: f64,  // The batch version called on the whole batch.
+}
+
+/// Measures the average time in nanoseconds of one call to the scrambler's
+/// `hash()`, in scalar form.
+pub fn ns_per_hash<S>(scrambler: &S, iterations: u32) -> f64
+where
+    S: OwenScrambler + ?Sized,
+{
+    time_scalar(iterations, |x, i| scrambler.hash(x, i))
+}
+
+/// Same as `ns_per_hash()`, but in batched form.
+pub fn ns_per_hash_batched<S>(scrambler: &S, iterations: u32) -> f64
+where
+    S: OwenScrambler + ?Sized,
+{
+    time_batched(iterations, |x, seed| scrambler.hash(x, seed))
+}
+
+/// Times a scrambler in both forms.
+pub fn bench_scrambler<S>(name: &str, scrambler: &S, iterations: u32) -> BenchResult
+where
+    S: OwenScrambler + ?Sized,
+{
+    BenchResult {
+        name: name.into(),
+        scalar_ns: ns_per_hash(scrambler, iterations),
+        batched_ns: ns_per_hash_batched(scrambler, iterations),
+    }
+}
+
+/// Runs the full benchmark suite: `sobol_u32()`, each named hash both
+/// hand-written and interpreted from its `HashOp`s via `exec_hash_slice()`,
+/// and the reference scramble.
+///
+/// The reference scramble is much slower than everything else, so it's run
+/// for 1/64th of the iterations.
+pub fn run_benchmarks(iterations: u32) -> Vec<BenchResult> {
+    let mut results = Vec::new();
+
+    results.push(BenchResult {
+        name: "sobol_u32".into(),
+        scalar_ns: time_scalar(iterations, |x, i| sobol_u32(i.wrapping_add(x & 1), 1)),
+        batched_ns: time_batched(iterations, |i, _| sobol_u32(i, 1)),
+    });
+
+    for hash in LkHash::ALL.iter() {
+        results.push(bench_scrambler(hash.name(), hash, iterations));
+        results.push(bench_scrambler(
+            &format!("{} (interpreted)", hash.name()),
+            hash.ops(),
+            iterations,
+        ));
+    }
+
+    results.push(bench_scrambler("ref", &Reference, (iterations / 64).max(1)));
+
+    results
+}
+
+/// Runs the batch API benchmarks: `sobol_u32_batch()`,
+/// `owen_scramble_fast_u32_batch()`, `exec_hash_slice_batch()` (with the
+/// "good" hash), and `u32_to_0_1_f32_batch()`, each against its scalar
+/// version at each of `API_BATCH_SIZES`.
+pub fn run_batch_benchmarks(iterations: u32) -> Vec<BatchBenchResult> {
+    let ops = black_box(LkHash::Good.ops());
+    let mut results = Vec::new();
+
+    for &size in API_BATCH_SIZES.iter() {
+        let result = |name: &str, scalar_ns, batch_ns| BatchBenchResult {
+            name: name.into(),
+            batch_size: size,
+            scalar_ns,
+            batch_ns,
+        };
+
+        results.push(result(
+            "sobol_u32",
+            time_slices(iterations, size, |xs, _| {
+                xs.iter_mut().for_each(|x| *x = sobol_u32(*x, 1))
+            }),
+            time_slices(iterations, size, |xs, _| sobol_u32_batch(xs, 1)),
+        ));
+
+        results.push(result(
+            "owen_scramble_fast_u32",
+            time_slices(iterations, size, |xs, seed| {
+                xs.iter_mut()
+                    .for_each(|x| *x = owen_scramble_fast_u32(*x, seed))
+            }),
+            time_slices(iterations, size, owen_scramble_fast_u32_batch),
+        ));
+
+        results.push(result(
+            "exec_hash_slice",
+            time_slices(iterations, size, |xs, seed| {
+                xs.iter_mut()
+                    .for_each(|x| *x = exec_hash_slice(ops, *x, seed))
+            }),
+            time_slices(iterations, size, |xs, seed| {
+                exec_hash_slice_batch(ops, xs, seed)
+            }),
+        ));
+
+        let mut out = vec![0.0f32; size];
+        let scalar_ns = time_slices(iterations, size, |xs, _| {
+            for (o, &x) in out.iter_mut().zip(xs.iter()) {
+                *o = u32_to_0_1_f32(x);
+            }
+            black_box(&mut out);
+        });
+        let batch_ns = time_slices(iterations, size, |xs, _| {
+            u32_to_0_1_f32_batch(xs, &mut out);
+            black_box(&mut out);
+        });
+        results.push(result("u32_to_0_1_f32", scalar_ns, batch_ns));
+    }
+
+    results
+}
+
+/// Runs the benchmark suite, and prints the results to the console.
+pub fn print_benchmarks(iterations: u32) {
+    println!("{:<24} {:>12} {:>12}", "", "scalar ns", "batched ns");
+    for result in run_benchmarks(iterations) {
+        println!(
+            "{:<24} {:>12.3} {:>12.3}",
+            result.name, result.scalar_ns, result.batched_ns
+        );
+    }
+
+    println!();
+    println!(
+        "{:<24} {:>6} {:>12} {:>12}",
+        "", "batch", "scalar ns", "batch ns"
+    );
+    for result in run_batch_benchmarks(iterations) {
+        println!(
+            "{:<24} {:>6} {:>12.3} {:>12.3}",
+            result.name, result.batch_size, result.scalar_ns, result.batch_ns
+        );
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// Times `f(x, i)` where `x` is the previous result and `i` is the
+/// iteration number.
+fn time_scalar<F>(iterations: u32, f: F) -> f64
+where
+    F: Fn(u32, u32) -> u32,
+{
+    let mut x = 0x9e3779b9u32;
+    let start = Instant::now();
+    for i in 0..iterations {
+        x = f(x, i);
+    }
+    let elapsed = start.elapsed();
+    black_box(x);
+
+    elapsed.as_secs_f64() * 1.0e9 / iterations.max(1) as f64
+}
+
+/// Times `f(x, round)` over batches of consecutive inputs `x`, with a new
+/// `round` number per batch.
+fn time_batched<F>(iterations: u32, f: F) -> f64
+where
+    F: Fn(u32, u32) -> u32,
+{
+    let rounds = (iterations as usize / BATCH_SIZE).max(1);
+    let mut buffer = vec![0u32; BATCH_SIZE];
+    let start = Instant::now();
+    for round in 0..rounds {
+        let base = (round * BATCH_SIZE) as u32;
+        for (i, out) in buffer.iter_mut().enumerate() {
+            *out = f(base.wrapping_add(i as u32), round as u32);
+        }
+        black_box(&mut buffer);
+    }
+    let elapsed = start.elapsed();
+
+    elapsed.as_secs_f64() * 1.0e9 / (rounds * BATCH_SIZE) as f64
+}
+
+/// Times `f(xs, round)` on batches of `batch_size` consecutive inputs `xs`,
+/// with a new `round` number per batch.  Results are per value.
+fn time_slices<F>(iterations: u32, batch_size: usize, mut f: F) -> f64
+where
+    F: FnMut(&mut [u32], u32),
+{
+    let rounds = (iterations as usize / batch_size).max(1);
+    let mut buffer = vec![0u32; batch_size];
+    let start = Instant::now();
+    for round in 0..rounds {
+        let base = (round * batch_size) as u32;
+        for (i, x) in buffer.iter_mut().enumerate() {
+            *x = base.wrapping_add(i as u32);
+        }
+        f(&mut buffer, round as u32);
+        black_box(&mut buffer);
+    }
+    let elapsed = start.elapsed();
+
+    elapsed.as_secs_f64() * 1.0e9 / (rounds * batch_size) as f64
+}
@@ -6,16 +6,27 @@ use rand::random;
 // to mean "use the passed seed".  This is because for all
 // operations a constant of zero is either effectively a no-op,
 // or it's completely invalid for this kind of hash anyway.
-#[derive(Debug, Copy, Clone)]
+//
+// All operations only propagate changes upward in bit significance,
+// which is what makes them valid for Owen scrambling.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum HashOp {
-    Nop,         // Do nothing
-    Xor(u32),    // x ^= constant
-    Add(u32),    // x += constant
-    Mul(u32),    // x *= odd_constant
-    ShlXor(u32), // x ^= x << constant[1, 31]
-    ShlAdd(u32), // x += x << constant[1, 31]
-    MulXor(u32), // x ^= x * even_constant
-    SeedMix,     // The super effective seed mixing approach.
+    Nop,             // Do nothing
+    Xor(u32),        // x ^= constant
+    Add(u32),        // x += constant
+    Mul(u32),        // x *= odd_constant
+    ShlXor(u32),     // x ^= x << constant[1, 31]
+    ShlAdd(u32),     // x += x << constant[1, 31]
+    MulXor(u32),     // x ^= x * even_constant
+    SeedMix,         // The super effective seed mixing approach.
+    Not,             // x = !x
+    Neg,             // x = -x
+    ShlSub(u32),     // x -= x << constant[1, 31]
+    MulSeedAdd(u32), // x = (x * odd_constant) + seed
+    MulSeedXor(u32), // x ^= (x * even_constant) + seed
+    SeedRotMul(u32), // x *= seed.rotate_right(constant[1, 31]) | 1
+    ShlMaskXor(u32), // x ^= (x << constant[1, 31]) & seed
+    ShlSeedXor(u32), // x ^= (x << constant[1, 31]) + seed
 }
 
 impl HashOp {
@@ -27,89 +38,14 @@ impl HashOp {
             random::<u32>()
         };
 
-        match random::<u32>() % 8 {
-            0 => HashOp::Add(constant),
-            1 => {
-                if constant == 0 {
-                    HashOp::Mul(0)
-                } else {
-                    HashOp::Mul(constant | 1)
-                }
-            }
-            2 => HashOp::MulXor(constant & !1),
-            3 => HashOp::Xor(constant),
-            4 => {
-                if constant == 0 {
-                    HashOp::ShlXor(0)
-                } else {
-                    HashOp::ShlXor((constant % 31) + 1)
-                }
-            }
-            5 => {
-                if constant == 0 {
-                    HashOp::ShlAdd(0)
-                } else {
-                    HashOp::ShlAdd((constant % 31) + 1)
-                }
-            }
-            6 => HashOp::SeedMix,
-            7 => HashOp::Nop,
-            _ => unreachable!(),
-        }
+        let kind = OpKind::ALL[random::<usize>() % OpKind::ALL.len()];
+        kind.with_constant(constant)
     }
 
     pub fn new_constant(&self) -> HashOp {
-        match *self {
-            HashOp::Nop => *self,
-            HashOp::SeedMix => *self,
-
-            HashOp::Xor(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::Xor(random::<u32>())
-                }
-            }
-
-            HashOp::Add(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::Add(random::<u32>())
-                }
-            }
-
-            HashOp::Mul(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::Mul(random::<u32>() | 1)
-                }
-            }
-
-            HashOp::ShlXor(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::ShlXor((random::<u32>() % 31) + 1)
-                }
-            }
-
-            HashOp::ShlAdd(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::ShlAdd((random::<u32>() % 31) + 1)
-                }
-            }
-
-            HashOp::MulXor(c) => {
-                if c == 0 {
-                    *self
-                } else {
-                    HashOp::MulXor(random::<u32>() & !1)
-                }
-            }
+        match self.constant() {
+            None | Some(0) => *self,
+            Some(_) => self.kind().with_constant(random::<u32>()),
         }
     }
 
@@ -169,19 +105,350 @@ impl HashOp {
                 let a = x.wrapping_add(seed);
                 a.wrapping_mul((seed >> 16) | 1)
             }
+
+            HashOp::Not => !x,
+
+            HashOp::Neg => x.wrapping_neg(),
+
+            HashOp::ShlSub(c) => x.wrapping_sub(x << shift_or_seed(c, seed)),
+
+            HashOp::MulSeedAdd(c) => {
+                let m = if c == 0 { seed | 1 } else { c };
+                x.wrapping_mul(m).wrapping_add(seed)
+            }
+
+            HashOp::MulSeedXor(c) => {
+                let m = if c == 0 { seed & !1 } else { c };
+                x ^ x.wrapping_mul(m).wrapping_add(seed)
+            }
+
+            HashOp::SeedRotMul(c) => x.wrapping_mul(seed.rotate_right(shift_or_seed(c, seed)) | 1),
+
+            HashOp::ShlMaskXor(c) => x ^ ((x << shift_or_seed(c, seed)) & seed),
+
+            HashOp::ShlSeedXor(c) => x ^ (x << shift_or_seed(c, seed)).wrapping_add(seed),
+        }
+    }
+
+    /// Whether `exec()` with the given seed is a bijection, and so can be
+    /// inverted.
+    ///
+    /// All ops are, except the seeded `ShlXor` and `ShlAdd` when the seed's
+    /// low five bits are zero, which is a shift by zero.
+    pub fn is_invertible(&self, seed: u32) -> bool {
+        match *self {
+            HashOp::ShlXor(0) | HashOp::ShlAdd(0) => seed & 0b11111 != 0,
+            _ => true,
+        }
+    }
+
+    /// The inverse of `exec()`: returns the `x` that `exec(x, seed)` maps to
+    /// `y`, or `None` if the op isn't invertible with this seed (see
+    /// `is_invertible()`).
+    pub fn exec_inverse(&self, y: u32, seed: u32) -> Option<u32> {
+        if !self.is_invertible(seed) {
+            return None;
+        }
+
+        let shift = |c: u32| if c == 0 { seed & 0b11111 } else { c };
+        let mul_by = |c: u32| if c == 0 { seed | 1 } else { c };
+        let mulxor_by = |c: u32| if c == 0 { seed & !1 } else { c };
+
+        let x = match *self {
+            HashOp::Nop => y,
+            HashOp::Xor(_) | HashOp::Not => self.exec(y, seed),
+            HashOp::Add(c) => y.wrapping_sub(if c == 0 { seed } else { c }),
+            HashOp::Mul(c) => y.wrapping_mul(mul_inverse(mul_by(c))),
+            HashOp::ShlXor(c) => invert_xor_fn(y, |x| x << shift(c)),
+            HashOp::ShlAdd(c) => y.wrapping_mul(mul_inverse(1u32.wrapping_add(1 << shift(c)))),
+            HashOp::MulXor(c) => invert_xor_fn(y, |x| x.wrapping_mul(mulxor_by(c))),
+            HashOp::SeedMix => y
+                .wrapping_mul(mul_inverse((seed >> 16) | 1))
+                .wrapping_sub(seed),
+            HashOp::Neg => y.wrapping_neg(),
+            HashOp::ShlSub(c) => {
+                y.wrapping_mul(mul_inverse(1u32.wrapping_sub(1 << shift_or_seed(c, seed))))
+            }
+            HashOp::MulSeedAdd(c) => y.wrapping_sub(seed).wrapping_mul(mul_inverse(mul_by(c))),
+            HashOp::MulSeedXor(c) => {
+                invert_xor_fn(y, |x| x.wrapping_mul(mulxor_by(c)).wrapping_add(seed))
+            }
+            HashOp::SeedRotMul(c) => {
+                y.wrapping_mul(mul_inverse(seed.rotate_right(shift_or_seed(c, seed)) | 1))
+            }
+            HashOp::ShlMaskXor(c) => invert_xor_fn(y, |x| (x << shift_or_seed(c, seed)) & seed),
+            HashOp::ShlSeedXor(c) => {
+                invert_xor_fn(y, |x| (x << shift_or_seed(c, seed)).wrapping_add(seed))
+            }
+        };
+        Some(x)
+    }
+
+    /// Estimated cost of executing this operation, under the given cost
+    /// model.
+    pub fn cost(&self, model: &CostModel) -> f64 {
+        let seed_cost = |c: u32| if c == 0 { model.seed_derive } else { 0.0 };
+        match *self {
+            HashOp::Nop => 0.0,
+            HashOp::Xor(_) => model.xor,
+            HashOp::Add(_) => model.add,
+            HashOp::Mul(c) => model.mul + seed_cost(c),
+            HashOp::ShlXor(c) => model.shift + model.xor + seed_cost(c),
+            HashOp::ShlAdd(c) => model.shift + model.add + seed_cost(c),
+            HashOp::MulXor(c) => model.mul + model.xor + seed_cost(c),
+            HashOp::SeedMix => model.add + model.mul + model.seed_derive * 2.0,
+            HashOp::Not => model.xor,
+            HashOp::Neg => model.add,
+            HashOp::ShlSub(c) => model.shift + model.add + seed_cost(c),
+            HashOp::MulSeedAdd(c) => model.mul + model.add + seed_cost(c),
+            HashOp::MulSeedXor(c) => model.mul + model.add + model.xor + seed_cost(c),
+            HashOp::SeedRotMul(c) => model.mul + model.seed_derive * 2.0 + seed_cost(c),
+            HashOp::ShlMaskXor(c) => model.shift + model.xor * 2.0 + seed_cost(c),
+            HashOp::ShlSeedXor(c) => model.shift + model.add + model.xor + seed_cost(c),
+        }
+    }
+
+    /// The kind of this operation, ignoring its constant.
+    pub fn kind(&self) -> OpKind {
+        match *self {
+            HashOp::Nop => OpKind::Nop,
+            HashOp::Xor(_) => OpKind::Xor,
+            HashOp::Add(_) => OpKind::Add,
+            HashOp::Mul(_) => OpKind::Mul,
+            HashOp::ShlXor(_) => OpKind::ShlXor,
+            HashOp::ShlAdd(_) => OpKind::ShlAdd,
+            HashOp::MulXor(_) => OpKind::MulXor,
+            HashOp::SeedMix => OpKind::SeedMix,
+            HashOp::Not => OpKind::Not,
+            HashOp::Neg => OpKind::Neg,
+            HashOp::ShlSub(_) => OpKind::ShlSub,
+            HashOp::MulSeedAdd(_) => OpKind::MulSeedAdd,
+            HashOp::MulSeedXor(_) => OpKind::MulSeedXor,
+            HashOp::SeedRotMul(_) => OpKind::SeedRotMul,
+            HashOp::ShlMaskXor(_) => OpKind::ShlMaskXor,
+            HashOp::ShlSeedXor(_) => OpKind::ShlSeedXor,
+        }
+    }
+
+    /// The constant of this operation, or `None` if its kind doesn't have
+    /// one.
+    pub fn constant(&self) -> Option<u32> {
+        match *self {
+            HashOp::Nop | HashOp::SeedMix | HashOp::Not | HashOp::Neg => None,
+            HashOp::Xor(c)
+            | HashOp::Add(c)
+            | HashOp::Mul(c)
+            | HashOp::ShlXor(c)
+            | HashOp::ShlAdd(c)
+            | HashOp::MulXor(c)
+            | HashOp::ShlSub(c)
+            | HashOp::MulSeedAdd(c)
+            | HashOp::MulSeedXor(c)
+            | HashOp::SeedRotMul(c)
+            | HashOp::ShlMaskXor(c)
+            | HashOp::ShlSeedXor(c) => Some(c),
         }
     }
 
     pub fn uses_mul_and_seed(&self) -> bool {
         match *self {
             HashOp::Nop => false,
-            HashOp::Xor(c) => false,
-            HashOp::Add(c) => false,
+            HashOp::Xor(_) => false,
+            HashOp::Add(_) => false,
             HashOp::Mul(c) => c == 0,
-            HashOp::ShlXor(c) => false,
-            HashOp::ShlAdd(c) => false,
+            HashOp::ShlXor(_) => false,
+            HashOp::ShlAdd(_) => false,
             HashOp::MulXor(c) => c == 0,
             HashOp::SeedMix => true,
+            HashOp::Not => false,
+            HashOp::Neg => false,
+            HashOp::ShlSub(_) => false,
+            HashOp::MulSeedAdd(c) => c == 0,
+            HashOp::MulSeedXor(c) => c == 0,
+            HashOp::SeedRotMul(_) => true,
+            HashOp::ShlMaskXor(_) => false,
+            HashOp::ShlSeedXor(_) => false,
+        }
+    }
+}
+
+/// The shift (or rotation) amount for the newer shifting ops: the constant,
+/// or if that's zero, an amount in [1, 31] taken from the seed.
+///
+/// Unlike the seeded `ShlXor` and `ShlAdd`, this never shifts by zero,
+/// which would make those ops non-invertible.
+#[inline(always)]
+fn shift_or_seed(c: u32, seed: u32) -> u32 {
+    if c == 0 {
+        (seed % 31) + 1
+    } else {
+        c
+    }
+}
+
+/// The multiplicative inverse of odd `m`, modulo 2^32.
+fn mul_inverse(m: u32) -> u32 {
+    // Newton's method.  `m` is its own inverse to three bits, and each
+    // iteration doubles the number of correct bits.
+    let mut inv = m;
+    for _ in 0..4 {
+        inv = inv.wrapping_mul(2u32.wrapping_sub(m.wrapping_mul(inv)));
+    }
+    inv
+}
+
+/// Inverts `y = x ^ f(x)`, where each bit of `f(x)` only depends on
+/// strictly lower bits of `x`.
+///
+/// Each iteration of `x = y ^ f(x)` gets at least one more low bit of `x`
+/// right, so this converges in at most 32 iterations.
+fn invert_xor_fn<F>(y: u32, f: F) -> u32
+where
+    F: Fn(u32) -> u32,
+{
+    let mut x = y;
+    for _ in 0..32 {
+        let next = y ^ f(x);
+        if next == x {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+/// The kinds of `HashOp`, without their constants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    Nop,
+    Xor,
+    Add,
+    Mul,
+    ShlXor,
+    ShlAdd,
+    MulXor,
+    SeedMix,
+    Not,
+    Neg,
+    ShlSub,
+    MulSeedAdd,
+    MulSeedXor,
+    SeedRotMul,
+    ShlMaskXor,
+    ShlSeedXor,
+}
+
+impl OpKind {
+    pub const ALL: &'static [OpKind] = &[
+        OpKind::Nop,
+        OpKind::Xor,
+        OpKind::Add,
+        OpKind::Mul,
+        OpKind::ShlXor,
+        OpKind::ShlAdd,
+        OpKind::MulXor,
+        OpKind::SeedMix,
+        OpKind::Not,
+        OpKind::Neg,
+        OpKind::ShlSub,
+        OpKind::MulSeedAdd,
+        OpKind::MulSeedXor,
+        OpKind::SeedRotMul,
+        OpKind::ShlMaskXor,
+        OpKind::ShlSeedXor,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            OpKind::Nop => "nop",
+            OpKind::Xor => "xor",
+            OpKind::Add => "add",
+            OpKind::Mul => "mul",
+            OpKind::ShlXor => "shlxor",
+            OpKind::ShlAdd => "shladd",
+            OpKind::MulXor => "mulxor",
+            OpKind::SeedMix => "seedmix",
+            OpKind::Not => "not",
+            OpKind::Neg => "neg",
+            OpKind::ShlSub => "shlsub",
+            OpKind::MulSeedAdd => "mulseedadd",
+            OpKind::MulSeedXor => "mulseedxor",
+            OpKind::SeedRotMul => "seedrotmul",
+            OpKind::ShlMaskXor => "shlmaskxor",
+            OpKind::ShlSeedXor => "shlseedxor",
+        }
+    }
+
+    /// The inverse of `name()`.
+    pub fn from_name(name: &str) -> Option<OpKind> {
+        OpKind::ALL.iter().copied().find(|k| k.name() == name)
+    }
+
+    /// Whether operations of this kind have a constant.
+    pub fn has_constant(&self) -> bool {
+        !matches!(
+            *self,
+            OpKind::Nop | OpKind::SeedMix | OpKind::Not | OpKind::Neg
+        )
+    }
+
+    /// Whether the constant of this kind is a shift (or rotation) amount
+    /// in [1, 31].
+    pub fn is_shift(&self) -> bool {
+        matches!(
+            *self,
+            OpKind::ShlXor
+                | OpKind::ShlAdd
+                | OpKind::ShlSub
+                | OpKind::SeedRotMul
+                | OpKind::ShlMaskXor
+                | OpKind::ShlSeedXor
+        )
+    }
+
+    /// Whether operations of this kind use the seed regardless of their
+    /// constant.
+    pub fn always_uses_seed(&self) -> bool {
+        matches!(
+            *self,
+            OpKind::SeedMix
+                | OpKind::MulSeedAdd
+                | OpKind::MulSeedXor
+                | OpKind::SeedRotMul
+                | OpKind::ShlMaskXor
+                | OpKind::ShlSeedXor
+        )
+    }
+
+    /// Builds an operation of this kind from an arbitrary `u32`, adjusting
+    /// it to be valid for the kind (e.g. odd for `Mul`).  As with `HashOp`,
+    /// zero means "use the seed".
+    pub fn with_constant(&self, c: u32) -> HashOp {
+        let shift = if c == 0 { 0 } else { ((c - 1) % 31) + 1 };
+        let odd = if c == 0 { 0 } else { c | 1 };
+        // Only zero means "use the seed", so 1 can't become 0 here.
+        let even = match c {
+            0 => 0,
+            1 => 2,
+            _ => c & !1,
+        };
+        match *self {
+            OpKind::Nop => HashOp::Nop,
+            OpKind::Xor => HashOp::Xor(c),
+            OpKind::Add => HashOp::Add(c),
+            OpKind::Mul => HashOp::Mul(odd),
+            OpKind::ShlXor => HashOp::ShlXor(shift),
+            OpKind::ShlAdd => HashOp::ShlAdd(shift),
+            OpKind::MulXor => HashOp::MulXor(even),
+            OpKind::SeedMix => HashOp::SeedMix,
+            OpKind::Not => HashOp::Not,
+            OpKind::Neg => HashOp::Neg,
+            OpKind::ShlSub => HashOp::ShlSub(shift),
+            OpKind::MulSeedAdd => HashOp::MulSeedAdd(odd),
+            OpKind::MulSeedXor => HashOp::MulSeedXor(even),
+            OpKind::SeedRotMul => HashOp::SeedRotMul(shift),
+            OpKind::ShlMaskXor => HashOp::ShlMaskXor(shift),
+            OpKind::ShlSeedXor => HashOp::ShlSeedXor(shift),
         }
     }
 }
@@ -195,3 +462,881 @@ pub fn exec_hash_slice(hash_ops: &[HashOp], x: u32, seed: u32) -> u32 {
     }
     x
 }
+
+/// Same as `exec_hash_slice()`, but hashes every value of `xs` in place,
+/// all with the same seed.
+///
+/// Each op is resolved for the seed once (as in `CompiledHash`) and applied
+/// to the whole slice before the next, which vectorizes well.
+pub fn exec_hash_slice_batch(hash_ops: &[HashOp], xs: &mut [u32], seed: u32) {
+    for &op in hash_ops.iter() {
+        HashStep::new(op, seed).exec_slice(xs);
+    }
+}
+
+/// The inverse of `exec_hash_slice()`: returns the `x` that the hash maps
+/// to `y` with the given seed, or `None` if any op isn't invertible with
+/// this seed.  See `HashOp::exec_inverse()`.
+pub fn exec_hash_slice_inverse(hash_ops: &[HashOp], y: u32, seed: u32) -> Option<u32> {
+    hash_ops
+        .iter()
+        .rev()
+        .try_fold(y, |x, op| op.exec_inverse(x, seed))
+}
+
+/// Estimated cost of a slice of `HashOp`s, under the given cost model.
+pub fn hash_slice_cost(hash_ops: &[HashOp], model: &CostModel) -> f64 {
+    hash_ops.iter().map(|op| op.cost(model)).sum()
+}
+
+/// Relative costs of the basic operations that `HashOp`s are built from,
+/// for estimating the cost of a hash without timing it.
+#[derive(Debug, Copy, Clone)]
+pub struct CostModel {
+    pub add: f64,
+    pub xor: f64,
+    pub shift: f64,
+    pub mul: f64,
+
+    // Cost of deriving an operand from the seed, e.g. `seed | 1` for a
+    // seed-dependent multiply.
+    pub seed_derive: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> CostModel {
+        CostModel {
+            add: 1.0,
+            xor: 1.0,
+            shift: 1.0,
+            mul: 3.0,
+            seed_derive: 1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for CostModel {
+    type Err = String;
+
+    /// Parses a comma-separated list of `name=cost` pairs, e.g.
+    /// "mul=4,seed=0.5".  Names are `add`, `xor`, `shift`, `mul`, and
+    /// `seed`.  Unlisted costs are left at their defaults.
+    fn from_str(text: &str) -> Result<CostModel, String> {
+        let mut model = CostModel::default();
+        for item in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = item.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let cost: f64 = parts
+                .next()
+                .and_then(|c| c.trim().parse().ok())
+                .ok_or_else(|| format!("invalid cost in \"{}\"", item))?;
+            match name {
+                "add" => model.add = cost,
+                "xor" => model.xor = cost,
+                "shift" => model.shift = cost,
+                "mul" => model.mul = cost,
+                "seed" => model.seed_derive = cost,
+                _ => return Err(format!("unknown operation \"{}\"", name)),
+            }
+        }
+        Ok(model)
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// A `HashOp` sequence compiled for fast evaluation of many values with the
+/// same seed, giving exactly the same results as `exec_hash_slice()`.
+///
+/// Every op is expressible as the single branch-free step
+/// `x = (x & keep) ^ ((x * mul + add) & mask)`, so compiling resolves each
+/// op to the parameters of that step.  Ops that don't depend on the seed
+/// are resolved once up front, and the rest once per seed by `seeded()`.
+/// Applying one step at a time to a whole slice of values then has no
+/// per-value dispatch, and vectorizes well.
+#[derive(Debug, Clone)]
+pub struct CompiledHash {
+    ops: Vec<HashOp>,
+    steps: Vec<HashStep>,
+    seeded_ops: Vec<usize>, // Indices of the ops that depend on the seed.
+}
+
+/// A compiled hash with its seed resolved.  See `CompiledHash::seeded()`.
+#[derive(Debug, Clone)]
+pub struct SeededHash {
+    steps: Vec<HashStep>,
+}
+
+impl CompiledHash {
+    pub fn new(hash_ops: &[HashOp]) -> CompiledHash {
+        let seeded_ops: Vec<usize> = (0..hash_ops.len())
+            .filter(|&i| {
+                let op = hash_ops[i];
+                op.kind().always_uses_seed() || op.constant() == Some(0)
+            })
+            .collect();
+        CompiledHash {
+            ops: hash_ops.into(),
+            steps: hash_ops.iter().map(|&op| HashStep::new(op, 0)).collect(),
+            seeded_ops,
+        }
+    }
+
+    /// The ops this was compiled from.
+    pub fn ops(&self) -> &[HashOp] {
+        &self.ops
+    }
+
+    /// Resolves the seed, for evaluating the hash with that seed.
+    pub fn seeded(&self, seed: u32) -> SeededHash {
+        let mut steps = self.steps.clone();
+        for &i in self.seeded_ops.iter() {
+            steps[i] = HashStep::new(self.ops[i], seed);
+        }
+        SeededHash { steps }
+    }
+}
+
+impl SeededHash {
+    #[inline]
+    pub fn hash(&self, x: u32) -> u32 {
+        self.steps.iter().fold(x, |x, step| step.exec(x))
+    }
+
+    /// Hashes every value of `xs` in place.
+    pub fn hash_slice(&self, xs: &mut [u32]) {
+        for step in self.steps.iter() {
+            step.exec_slice(xs);
+        }
+    }
+}
+
+/// A single op of a compiled hash: `x = (x & keep) ^ ((x * mul + add) & mask)`.
+#[derive(Debug, Copy, Clone)]
+struct HashStep {
+    keep: u32,
+    mul: u32,
+    add: u32,
+    mask: u32,
+}
+
+impl HashStep {
+    /// Resolves `op` with the given seed.
+    fn new(op: HashOp, seed: u32) -> HashStep {
+        let or_seed = |c: u32, s: u32| if c == 0 { s } else { c };
+        let shl = |shift: u32| 1u32 << shift;
+        let (keep, mul, add, mask) = match op {
+            HashOp::Nop => (0, 1, 0, !0),
+            HashOp::Xor(c) => (!0, 0, or_seed(c, seed), !0),
+            HashOp::Add(c) => (0, 1, or_seed(c, seed), !0),
+            HashOp::Mul(c) => (0, or_seed(c, seed | 1), 0, !0),
+            HashOp::ShlXor(c) => (!0, shl(or_seed(c, seed & 0b11111)), 0, !0),
+            HashOp::ShlAdd(c) => {
+                let m = shl(or_seed(c, seed & 0b11111)).wrapping_add(1);
+                (0, m, 0, !0)
+            }
+            HashOp::MulXor(c) => (!0, or_seed(c, seed & !1), 0, !0),
+            HashOp::SeedMix => {
+                let m = (seed >> 16) | 1;
+                (0, m, seed.wrapping_mul(m), !0)
+            }
+            HashOp::Not => (!0, 0, !0, !0),
+            HashOp::Neg => (0, !0, 0, !0),
+            HashOp::ShlSub(c) => {
+                let m = 1u32.wrapping_sub(shl(shift_or_seed(c, seed)));
+                (0, m, 0, !0)
+            }
+            HashOp::MulSeedAdd(c) => (0, or_seed(c, seed | 1), seed, !0),
+            HashOp::MulSeedXor(c) => (!0, or_seed(c, seed & !1), seed, !0),
+            HashOp::SeedRotMul(c) => (0, seed.rotate_right(shift_or_seed(c, seed)) | 1, 0, !0),
+            HashOp::ShlMaskXor(c) => (!0, shl(shift_or_seed(c, seed)), 0, seed),
+            HashOp::ShlSeedXor(c) => (!0, shl(shift_or_seed(c, seed)), seed, !0),
+        };
+        HashStep {
+            keep,
+            mul,
+            add,
+            mask,
+        }
+    }
+
+    #[inline(always)]
+    fn exec(&self, x: u32) -> u32 {
+        (x & self.keep) ^ (x.wrapping_mul(self.mul).wrapping_add(self.add) & self.mask)
+    }
+
+    fn exec_slice(&self, xs: &mut [u32]) {
+        let step = *self;
+        for x in xs.iter_mut() {
+            *x = step.exec(*x);
+        }
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// Simplifies a slice of `HashOp`s to an equivalent (for all inputs and
+/// seeds) and usually cheaper canonical form.
+///
+/// This removes `Nop`s and multiplies by one, merges consecutive constant
+/// `Xor`s and `Add`s (dropping them if they cancel out), cancels pairs of
+/// seed `Xor`s, and folds consecutive constant multiplies (including
+/// `ShlAdd`, `ShlSub` and `Neg`, which are multiplies by `1 + 2^c`,
+/// `1 - 2^c` and `-1`) into one, or into a following `MulSeedAdd`.  `Not`
+/// is treated as `Xor(0xffffffff)` so that it merges too.  Constants that
+/// have a cheaper equivalent op are then rewritten: `Mul(-1)` to `Neg`,
+/// `Mul(1 + 2^c)` to `ShlAdd(c)`, `Mul(1 - 2^c)` to `ShlSub(c)`, and
+/// `MulXor(2^c)` to `ShlXor(c)`.
+///
+/// Hashes that simplify to the same ops are the same hash, so this can also
+/// be used to detect duplicates.
+pub fn simplify_hash(hash_ops: &[HashOp]) -> Vec<HashOp> {
+    let mut simplified: Vec<HashOp> = Vec::with_capacity(hash_ops.len());
+    for &op in hash_ops.iter() {
+        let mut next = normalize_op(op);
+
+        // Keep merging with the previous op for as long as possible.
+        while let (Some(op), Some(&prev)) = (next, simplified.last()) {
+            match merge_ops(prev, op) {
+                Some(merged) => {
+                    simplified.pop();
+                    next = merged.and_then(normalize_op);
+                }
+                None => break,
+            }
+        }
+
+        if let Some(op) = next {
+            simplified.push(op);
+        }
+    }
+    simplified
+}
+
+/// Rewrites a single op into its cheapest equivalent form, or `None` if it
+/// does nothing.
+fn normalize_op(op: HashOp) -> Option<HashOp> {
+    match op {
+        HashOp::Nop | HashOp::Mul(1) => None,
+        HashOp::Not => Some(HashOp::Xor(!0)),
+        HashOp::Mul(c) if c == !0 => Some(HashOp::Neg),
+        HashOp::Mul(c) if c > 1 && c & 1 == 1 && (c - 1).is_power_of_two() => {
+            Some(HashOp::ShlAdd((c - 1).trailing_zeros()))
+        }
+        HashOp::Mul(c) if c > 1 && c & 1 == 1 && 1u32.wrapping_sub(c).is_power_of_two() => {
+            Some(HashOp::ShlSub(1u32.wrapping_sub(c).trailing_zeros()))
+        }
+        HashOp::MulSeedAdd(1) => Some(HashOp::Add(0)),
+        HashOp::MulXor(c) if c > 1 && c.is_power_of_two() => {
+            Some(HashOp::ShlXor(c.trailing_zeros()))
+        }
+        _ => Some(op),
+    }
+}
+
+/// Merges two consecutive (normalized) ops into one.  Returns `None` if they
+/// can't be merged, and `Some(None)` if they cancel out completely.
+fn merge_ops(a: HashOp, b: HashOp) -> Option<Option<HashOp>> {
+    // The constant multiplier of an op, if it's a plain constant multiply.
+    let multiplier = |op: HashOp| match op {
+        HashOp::Mul(c) if c != 0 => Some(c),
+        HashOp::ShlAdd(c) if c != 0 => Some(1u32.wrapping_add(1 << c)),
+        HashOp::ShlSub(c) if c != 0 => Some(1u32.wrapping_sub(1 << c)),
+        HashOp::Neg => Some(!0),
+        _ => None,
+    };
+
+    match (a, b) {
+        (HashOp::Xor(0), HashOp::Xor(0)) => Some(None),
+        (HashOp::Xor(c1), HashOp::Xor(c2)) if c1 != 0 && c2 != 0 => {
+            Some(Some(HashOp::Xor(c1 ^ c2)).filter(|_| c1 != c2))
+        }
+        (HashOp::Add(c1), HashOp::Add(c2)) if c1 != 0 && c2 != 0 => {
+            let c = c1.wrapping_add(c2);
+            Some(Some(HashOp::Add(c)).filter(|_| c != 0))
+        }
+        (_, HashOp::MulSeedAdd(c2)) if c2 != 0 => {
+            multiplier(a).map(|c1| Some(HashOp::MulSeedAdd(c1.wrapping_mul(c2))))
+        }
+        _ => match (multiplier(a), multiplier(b)) {
+            (Some(c1), Some(c2)) => Some(Some(HashOp::Mul(c1.wrapping_mul(c2)))),
+            _ => None,
+        },
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// A template for generating random hashes, as used by the hash search.
+///
+/// Templates are written as a sequence of slots, optionally separated by
+/// semicolons.  Each slot is either:
+///
+/// - `?`: any random operation, as generated by `HashOp::gen_random()`.
+/// - An operation kind (see `OpKind::name()`), or several separated by `|`
+///   to pick randomly between them, followed by an optional operand for
+///   kinds that have a constant:
+///     - `?`: a random constant (the default if no operand is given).
+///     - `seed`: use the seed.
+///     - A number, e.g. `5` or `0x9e3779b9`: that exact constant.
+///     - A range, e.g. `1..8` or `0x100..=0xfff`: a random constant in it.
+///
+///   Since a constant of zero means "use the seed" (see `HashOp`), numbers
+///   and ranges can't include zero.  Use `seed` for that instead.
+///
+/// Constants are adjusted to be valid for their operation, as in
+/// `OpKind::with_constant()`.  For example, `mulxor ?; seedmix; mulxor ?`
+/// or `? ? ? ?` or `shlxor|shladd 1..=4; mul seed`.
+#[derive(Debug, Clone)]
+pub struct HashTemplate {
+    pub slots: Vec<TemplateSlot>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TemplateSlot {
+    Any,
+    Op {
+        kinds: Vec<OpKind>,
+        operand: Operand,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Operand {
+    Random,
+    Seed,
+    Range(u32, u32), // Inclusive.
+}
+
+impl HashTemplate {
+    /// Generates a random hash from the template.
+    pub fn generate(&self) -> Vec<HashOp> {
+        self.slots
+            .iter()
+            .map(|slot| match slot {
+                TemplateSlot::Any => HashOp::gen_random(),
+                TemplateSlot::Op { kinds, operand } => {
+                    let kind = kinds[random::<usize>() % kinds.len()];
+                    let c = match *operand {
+                        Operand::Random => random::<u32>(),
+                        Operand::Seed => 0,
+                        Operand::Range(a, b) => {
+                            let span = b as u64 - a as u64 + 1;
+                            (a as u64 + (random::<u64>() % span)) as u32
+                        }
+                    };
+                    kind.with_constant(c)
+                }
+            })
+            .collect()
+    }
+
+    /// The hash of a template that has every slot fixed: a single kind,
+    /// with the seed or a single value as its operand (if it has one).
+    /// Returns an error naming the first slot that isn't fixed.
+    pub fn fixed_ops(&self) -> Result<Vec<HashOp>, String> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let fixed = match slot {
+                    TemplateSlot::Op { kinds, operand } if kinds.len() == 1 => {
+                        match (kinds[0].has_constant(), *operand) {
+                            (false, _) => Some(kinds[0].with_constant(0)),
+                            (true, Operand::Seed) => Some(kinds[0].with_constant(0)),
+                            (true, Operand::Range(a, b)) if a == b => {
+                                Some(kinds[0].with_constant(a))
+                            }
+                            (true, _) => None,
+                        }
+                    }
+                    _ => None,
+                };
+                fixed.ok_or_else(|| {
+                    let slot = HashTemplate {
+                        slots: vec![slot.clone()],
+                    };
+                    format!("operation {} (\"{}\") is not fixed", i + 1, slot)
+                })
+            })
+            .collect()
+    }
+}
+
+impl std::str::FromStr for HashTemplate {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<HashTemplate, String> {
+        let mut slots = Vec::new();
+        let mut tokens = text
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .filter(|t| !t.is_empty())
+            .peekable();
+
+        while let Some(token) = tokens.next() {
+            if token == "?" {
+                slots.push(TemplateSlot::Any);
+                continue;
+            }
+
+            let kinds = token
+                .split('|')
+                .map(|name| {
+                    OpKind::from_name(&name.to_ascii_lowercase())
+                        .ok_or_else(|| format!("unknown operation \"{}\"", name))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Parse the operand, if any.
+            let mut operand = Operand::Random;
+            let mut operand_text = "";
+            if kinds.iter().any(|k| k.has_constant()) {
+                if let Some(&next) = tokens.peek() {
+                    if let Some(op) = parse_operand(next)? {
+                        operand = op;
+                        operand_text = next;
+                        tokens.next();
+                    }
+                }
+            }
+
+            // Shift amounts must be in [1, 31], and other constants can't
+            // be zero, which would silently use the seed instead.
+            if let Operand::Range(a, b) = operand {
+                if kinds.iter().any(|k| k.is_shift()) && (a < 1 || b > 31) {
+                    return Err(format!("shift amounts must be in 1..=31 in \"{}\"", token));
+                }
+                if a == 0 {
+                    return Err(format!(
+                        "constants can't be 0 in \"{} {}\" (use \"seed\" for the seed)",
+                        token, operand_text
+                    ));
+                }
+            }
+
+            slots.push(TemplateSlot::Op { kinds, operand });
+        }
+
+        if slots.is_empty() {
+            return Err("empty template".into());
+        }
+        Ok(HashTemplate { slots })
+    }
+}
+
+impl std::fmt::Display for HashTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, slot) in self.slots.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            match slot {
+                TemplateSlot::Any => write!(f, "?")?,
+                TemplateSlot::Op { kinds, operand } => {
+                    let names: Vec<_> = kinds.iter().map(|k| k.name()).collect();
+                    write!(f, "{}", names.join("|"))?;
+                    if kinds.iter().any(|k| k.has_constant()) {
+                        match *operand {
+                            Operand::Random => write!(f, " ?")?,
+                            Operand::Seed => write!(f, " seed")?,
+                            Operand::Range(a, b) if a == b => write!(f, " {}", a)?,
+                            Operand::Range(a, b) => write!(f, " {}..={}", a, b)?,
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Enumerates all hash structures of one to `max_len` operations.
+///
+/// A structure is a template where every slot is a single operation kind
+/// that either uses the seed, uses a random constant, or (for shifts) uses
+/// one of the given shift amounts.  `Nop` is never used.
+///
+/// Structures that don't use the seed at all are skipped, as are
+/// structures that are trivially equivalent to another enumerated
+/// structure: adjacent operations that merge into one (e.g. two constant
+/// xors), and adjacent operations that commute, which are only enumerated
+/// in one order.
+pub fn enumerate_structures(max_len: usize, shifts: &[u32]) -> Vec<HashTemplate> {
+    // All the choices for a single slot.
+    let mut choices = Vec::new();
+    for &kind in OpKind::ALL.iter() {
+        match kind {
+            OpKind::Nop => {}
+            _ if !kind.has_constant() => choices.push((kind, Operand::Random)),
+            _ if kind.is_shift() => {
+                choices.push((kind, Operand::Seed));
+                for &shift in shifts.iter().filter(|&&s| (1..=31).contains(&s)) {
+                    choices.push((kind, Operand::Range(shift, shift)));
+                }
+            }
+            _ => {
+                choices.push((kind, Operand::Seed));
+                choices.push((kind, Operand::Random));
+            }
+        }
+    }
+
+    let mut structures = Vec::new();
+    let mut indices = Vec::new();
+    for len in 1..=max_len {
+        indices.clear();
+        indices.resize(len, 0);
+        loop {
+            let ops: Vec<(OpKind, Operand)> = indices.iter().map(|&i| choices[i]).collect();
+            if is_canonical_structure(&ops) {
+                structures.push(HashTemplate {
+                    slots: ops
+                        .iter()
+                        .map(|&(kind, operand)| TemplateSlot::Op {
+                            kinds: vec![kind],
+                            operand,
+                        })
+                        .collect(),
+                });
+            }
+
+            // Increment the indices, odometer style.
+            let mut i = 0;
+            while i < len {
+                indices[i] += 1;
+                if indices[i] < choices.len() {
+                    break;
+                }
+                indices[i] = 0;
+                i += 1;
+            }
+            if i == len {
+                break;
+            }
+        }
+    }
+
+    structures
+}
+
+/// See `enumerate_structures()` for what this checks.
+fn is_canonical_structure(ops: &[(OpKind, Operand)]) -> bool {
+    use OpKind::*;
+    use Operand::*;
+
+    let uses_seed = ops
+        .iter()
+        .any(|(kind, operand)| kind.always_uses_seed() || matches!(operand, Seed));
+    if !uses_seed {
+        return false;
+    }
+
+    for pair in ops.windows(2) {
+        let redundant = match (pair[0], pair[1]) {
+            // Merge into a single operation, or cancel out.
+            ((Xor, Random), (Xor, Random)) => true,
+            ((Add, Random), (Add, Random)) => true,
+            ((Xor, Seed), (Xor, Seed)) => true,
+            ((Mul, Random), (Mul, Random)) => true,
+            ((Mul, Random), (ShlAdd, Range(..))) => true,
+            ((ShlAdd, Range(..)), (Mul, Random)) => true,
+            ((ShlXor, Range(a, _)), (ShlXor, Range(b, _))) if a == b => true,
+            ((Mul, Random), (ShlSub, Range(..))) => true,
+            ((ShlSub, Range(..)), (Mul, Random)) => true,
+            ((Mul, Random), (MulSeedAdd, Random)) => true,
+            ((Mul, Random), (Neg, _)) | ((Neg, _), (Mul, Random)) => true,
+            ((Not, _), (Not, _)) | ((Neg, _), (Neg, _)) => true,
+            ((Not, _), (Neg, _)) | ((Neg, _), (Not, _)) => true,
+            ((Not, _), (Xor, Random)) | ((Xor, Random), (Not, _)) => true,
+
+            // Commute, so only keep one order.
+            ((ShlXor, Range(a, _)), (ShlXor, Range(b, _))) => a > b,
+            ((ShlAdd, Range(a, _)), (ShlAdd, Range(b, _))) => a > b,
+            ((ShlSub, Range(a, _)), (ShlSub, Range(b, _))) => a > b,
+            ((ShlSub, Range(..)), (ShlAdd, Range(..))) => true,
+            ((Add, Random), (Not, _)) => true,
+            ((Xor, Seed), (Xor, Random)) => true,
+            ((Add, Seed), (Add, Random)) => true,
+
+            _ => false,
+        };
+        if redundant {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Parses a template operand.  Returns `None` if the token isn't an operand
+/// at all (i.e. it's the next slot).
+fn parse_operand(token: &str) -> Result<Option<Operand>, String> {
+    fn parse_number(text: &str) -> Result<u32, String> {
+        let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+            u32::from_str_radix(&text[2..], 16)
+        } else {
+            text.parse()
+        };
+        parsed.map_err(|_| format!("invalid constant \"{}\"", text))
+    }
+
+    if token == "?" {
+        Ok(Some(Operand::Random))
+    } else if token == "seed" {
+        Ok(Some(Operand::Seed))
+    } else if !token.starts_with(|c: char| c.is_ascii_digit()) {
+        Ok(None)
+    } else if let Some(i) = token.find("..") {
+        let a = parse_number(&token[..i])?;
+        let b = if token[i..].starts_with("..=") {
+            parse_number(&token[(i + 3)..])?
+        } else {
+            parse_number(&token[(i + 2)..])?
+                .checked_sub(1)
+                .ok_or_else(|| format!("empty range \"{}\"", token))?
+        };
+        if b < a {
+            return Err(format!("empty range \"{}\"", token));
+        }
+        Ok(Some(Operand::Range(a, b)))
+    } else {
+        let c = parse_number(token)?;
+        Ok(Some(Operand::Range(c, c)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_u32;
+    use crate::scramble::{LkHash, OwenScrambler};
+
+    const SEEDS: &[u32] = &[0, 1, 2, 31, 32, 0x10000, 0xffffffff, 0x9e3779b9];
+
+    /// Checks a compiled hash and `exec_hash_slice_batch()` against
+    /// `exec_hash_slice()` for the given seeds, plus some random ones.
+    fn check_compiled(hash_ops: &[HashOp], test: u32) {
+        let compiled = CompiledHash::new(hash_ops);
+        let inputs: Vec<u32> = (0..256).map(|i| hash_u32(i, test)).collect();
+        let random_seeds = (0..8).map(|i| hash_u32(i, !test));
+        for seed in SEEDS.iter().copied().chain(random_seeds) {
+            let expected: Vec<u32> = inputs
+                .iter()
+                .map(|&x| exec_hash_slice(hash_ops, x, seed))
+                .collect();
+
+            let seeded = compiled.seeded(seed);
+            let single: Vec<u32> = inputs.iter().map(|&x| seeded.hash(x)).collect();
+            assert_eq!(single, expected, "{:?} with seed {:#x}", hash_ops, seed);
+
+            let mut batch = inputs.clone();
+            seeded.hash_slice(&mut batch);
+            assert_eq!(batch, expected, "{:?} with seed {:#x}", hash_ops, seed);
+
+            let mut batch = inputs.clone();
+            compiled.hash_slice(&mut batch, seed);
+            assert_eq!(batch, expected, "{:?} with seed {:#x}", hash_ops, seed);
+
+            let mut batch = inputs.clone();
+            exec_hash_slice_batch(hash_ops, &mut batch, seed);
+            assert_eq!(batch, expected, "{:?} with seed {:#x}", hash_ops, seed);
+        }
+    }
+
+    #[test]
+    fn compiled_single_ops() {
+        for (i, kind) in OpKind::ALL.iter().enumerate() {
+            for &c in [0, 1, 2, 5, 31, 0x788aeeed, 0x41506a02, 0xffffffff].iter() {
+                check_compiled(&[kind.with_constant(c)], i as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn compiled_named_hashes() {
+        for (i, hash) in LkHash::ALL.iter().enumerate() {
+            check_compiled(hash.ops(), i as u32);
+        }
+    }
+
+    /// A random sequence of up to 8 ops, deterministic in `test`.
+    fn random_ops(test: u32) -> Vec<HashOp> {
+        let len = 1 + hash_u32(test, 0) as usize % 8;
+        (0..len)
+            .map(|i| {
+                let r = hash_u32(test, i as u32 + 1);
+                let kind = OpKind::ALL[r as usize % OpKind::ALL.len()];
+                // Use the seed a quarter of the time.
+                let c = if r >> 30 == 0 { 0 } else { hash_u32(r, test) };
+                kind.with_constant(c)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compiled_random_sequences() {
+        for test in 0..1000 {
+            check_compiled(&random_ops(test), test);
+        }
+    }
+
+    #[test]
+    fn inverse_single_ops() {
+        for kind in OpKind::ALL.iter() {
+            for &c in [0, 1, 2, 5, 31, 0x788aeeed, 0x41506a02, 0xffffffff].iter() {
+                let op = kind.with_constant(c);
+                let random_seeds = (0..8).map(|i| hash_u32(i, c));
+                for seed in SEEDS.iter().copied().chain(random_seeds) {
+                    for i in 0..256 {
+                        let x = hash_u32(i, seed);
+                        let inverse = op.exec_inverse(op.exec(x, seed), seed);
+                        if op.is_invertible(seed) {
+                            assert_eq!(inverse, Some(x), "{:?} with seed {:#x}", op, seed);
+                        } else {
+                            assert_eq!(inverse, None, "{:?} with seed {:#x}", op, seed);
+                        }
+                    }
+                }
+            }
+        }
+
+        // The seeded shifts by zero.
+        assert!(!HashOp::ShlXor(0).is_invertible(32));
+        assert!(!HashOp::ShlAdd(0).is_invertible(0));
+    }
+
+    #[test]
+    fn inverse_random_sequences() {
+        for test in 0..1000 {
+            let hash_ops = random_ops(test);
+            for seed in SEEDS.iter().copied() {
+                let invertible = hash_ops.iter().all(|op| op.is_invertible(seed));
+                for i in 0..64 {
+                    let x = hash_u32(i, test);
+                    let y = exec_hash_slice(&hash_ops, x, seed);
+                    let expected = if invertible { Some(x) } else { None };
+                    assert_eq!(exec_hash_slice_inverse(&hash_ops, y, seed), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simplify_random_sequences() {
+        for test in 0..1000 {
+            let hash_ops = random_ops(test);
+            let simplified = simplify_hash(&hash_ops);
+            let random_seeds = (0..8).map(|i| hash_u32(i, !test));
+            for seed in SEEDS.iter().copied().chain(random_seeds) {
+                for i in 0..256 {
+                    let x = hash_u32(i, test ^ seed);
+                    assert_eq!(
+                        exec_hash_slice(&simplified, x, seed),
+                        exec_hash_slice(&hash_ops, x, seed),
+                        "{:?} simplified to {:?}, with x {:#x} and seed {:#x}",
+                        hash_ops,
+                        simplified,
+                        x,
+                        seed
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn template_parsing() {
+        let parse = |text: &str| text.parse::<HashTemplate>();
+        for &(text, expected) in &[
+            ("mulxor ?; seedmix; mulxor", "mulxor ?; seedmix; mulxor ?"),
+            ("? ?", "?; ?"),
+            (
+                "SHLXOR|shladd 1..4 mul seed",
+                "shlxor|shladd 1..=3; mul seed",
+            ),
+            ("xor 0x10; not", "xor 16; not"),
+        ] {
+            assert_eq!(parse(text).unwrap().to_string(), expected);
+        }
+
+        let fixed = parse("xor 5; mul seed; seedmix").unwrap().fixed_ops();
+        assert_eq!(
+            fixed,
+            Ok(vec![HashOp::Xor(5), HashOp::Mul(0), HashOp::SeedMix])
+        );
+        assert!(parse("xor 5; mul ?").unwrap().fixed_ops().is_err());
+
+        let ranged = parse("add 3..=5").unwrap();
+        for _ in 0..100 {
+            let c = ranged.generate()[0].constant().unwrap();
+            assert!((3..=5).contains(&c), "{}", c);
+        }
+
+        for &bad in &[
+            "",
+            ";",
+            "foo",
+            "xor 0",
+            "mul 0..16",
+            "shlxor 0",
+            "shlxor 1..=32",
+            "add 5..5",
+            "add 5..=4",
+            "add 0xzz",
+        ] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn canonical_structures() {
+        use OpKind::*;
+        use Operand::*;
+
+        for &(ops, expected) in &[
+            (&[(Add, Seed)][..], true),
+            (&[(SeedMix, Random)][..], true),
+            // Doesn't use the seed.
+            (&[(Xor, Random), (Mul, Random)][..], false),
+            // Merge into one op.
+            (&[(Xor, Random), (Xor, Random), (Add, Seed)][..], false),
+            (&[(Xor, Seed), (Xor, Seed)][..], false),
+            (&[(Not, Random), (Neg, Random), (Add, Seed)][..], false),
+            // Commute, so only one order is canonical.
+            (
+                &[(ShlXor, Range(3, 3)), (ShlXor, Range(1, 1)), (Add, Seed)][..],
+                false,
+            ),
+            (
+                &[(ShlXor, Range(1, 1)), (ShlXor, Range(3, 3)), (Add, Seed)][..],
+                true,
+            ),
+            (&[(Xor, Seed), (Xor, Random)][..], false),
+            (&[(Xor, Random), (Xor, Seed)][..], true),
+        ] {
+            assert_eq!(is_canonical_structure(ops), expected, "{:?}", ops);
+        }
+    }
+
+    #[test]
+    fn enumerated_structures() {
+        let structures: Vec<String> = enumerate_structures(3, &[1, 3])
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut unique = structures.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), structures.len());
+
+        let has = |text: &str| structures.iter().any(|s| s == text);
+        assert!(has("add seed"));
+        assert!(has("mulxor ?; seedmix; mulxor ?"));
+        assert!(!has("xor ?"));
+        assert!(!has("nop; add seed"));
+        assert!(!has("xor ?; xor ?; add seed"));
+        assert!(has("shlxor 1; shlxor 3; add seed"));
+        assert!(!has("shlxor 3; shlxor 1; add seed"));
+        assert!(!has("shlxor 2; add seed"));
+    }
+}
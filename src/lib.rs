@@ -0,0 +1,59 @@
+//! Owen-scrambled Sobol sampling, and tools for building and evaluating the
+//! hashes used to do fast hash-based Owen scrambling.
+//!
+//! - `sobol`: Sobol sampling, with both fast hash-based and "ground truth"
+//!   reference Owen scrambling.
+//! - `scramble`: the `OwenScrambler` trait, implemented by all of the
+//!   scrambling approaches, including the named Laine-Karras style hashes.
+//! - `hash_gen`: hashes built from sequences of `HashOp`s, as used when
+//!   searching for new scrambling hashes, and compiling them for fast
+//!   evaluation.
+//! - `stats`: measuring, scoring, and visualizing the statistics of a
+//!   scrambling hash.
+//! - `search`: searching for new scrambling hashes with those scores.
+//! - `pointset`: quality metrics for point sets, such as discrepancy, and
+//!   images of them.
+//! - `report`: reports comparing several scrambling hashes.
+//! - `bench`: simple timing of scrambling hashes.
+//! - `plot`: simple drawing utilities used for the images.
+
+pub mod bench;
+pub mod hash_gen;
+pub mod plot;
+pub mod pointset;
+pub mod report;
+pub mod scramble;
+pub mod search;
+pub mod sobol;
+pub mod stats;
+
+/// A high-quality general-purpose 32-bit hash, seedable.
+///
+/// This is *not* an Owen-scrambling hash.  It's used for e.g. randomizing
+/// seeds before they're passed to the scrambling hashes.
+pub fn hash_u32(n: u32, seed: u32) -> u32 {
+    // Seeding.
+    let mut n = 0x6217c6e1 ^ n.wrapping_add(seed.wrapping_mul(0x9e3779b9));
+
+    // From https://github.com/skeeto/hash-prospector
+    n ^= n >> 17;
+    n = n.wrapping_mul(0xed5ad4bb);
+    n ^= n >> 11;
+    n = n.wrapping_mul(0xac4c1b51);
+    n ^= n >> 15;
+    n = n.wrapping_mul(0x31848bab);
+    n ^= n >> 14;
+
+    n
+}
+
+/// Combines a seed with another value into a new seed, the way renderers
+/// commonly derive e.g. per-dimension seeds.
+///
+/// This is the classic `boost::hash_combine()`.
+pub fn hash_combine(seed: u32, v: u32) -> u32 {
+    seed ^ (v
+        .wrapping_add(0x9e3779b9)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2))
+}
@@ -1,355 +1,653 @@
-#![allow(unused)]
-
-mod hash_gen;
-mod sobol;
-mod stats;
-
 use std::fs::File;
-use std::io::Write;
-
-use hash_gen::{exec_hash_slice, HashOp};
-use stats::{measure_stats, print_stats, write_stats_image, Stats, STATS_ZERO};
+use std::path::Path;
+
+use sample_test::{
+    bench::print_benchmarks,
+    hash_gen::{exec_hash_slice, HashTemplate},
+    pointset::{
+        generate_projection_matrix_image, generate_samples_animation, generate_samples_image,
+    },
+    report::{print_correlation_table, write_comparison_report, CompareConfig},
+    scramble::{find_counterexample, FnScrambler, LkHash, OwenScrambler, Reference, Unscrambled},
+    search::{rank_structures, search_hashes, SearchConfig},
+    sobol,
+    stats::{measure_stats, print_stats, write_stats_image, write_stats_svg},
+};
 
 fn main() {
-    // Set rayon per-thread stack size, because by default it's too small
-    // for what we're doing.
-    rayon::ThreadPoolBuilder::new()
-        .stack_size(1024 * 1024 * 16)
-        .build_global()
-        .unwrap();
-
     // Parse command line arguments.
-    let args = clap::App::new("Sample Testing")
+    use clap::{App, AppSettings, Arg, SubCommand};
+    let hash_arg = |default| {
+        Arg::with_name("hash")
+            .long("hash")
+            .value_name("NAME")
+            .default_value(default)
+            .help("Which scramble to use: lk, v2, fast, good, fixed, ref, or raw")
+    };
+    let number_arg = |name: &'static str, default, help| {
+        Arg::with_name(name)
+            .long(name)
+            .value_name("N")
+            .default_value(default)
+            .help(help)
+    };
+    let output_arg = |long: &'static str, default, help| {
+        Arg::with_name(long)
+            .long(long)
+            .value_name("PATH")
+            .default_value(default)
+            .help(help)
+    };
+    let seed_source_arg = || {
+        Arg::with_name("seed-source")
+            .long("seed-source")
+            .value_name("SOURCE")
+            .default_value("random")
+            .help(
+                "Seeds to measure stats with: random, sequential, low-popcount, hash-combine, \
+                 or gray-code",
+            )
+    };
+    let score_arg = || {
+        Arg::with_name("score")
+            .long("score")
+            .value_name("WEIGHTS")
+            .default_value("")
+            .help(
+                "Scoring weights, e.g. \"avalanche=1,bias=1,tree=0,discrepancy=0,variance=0\", \
+                 plus \"points\" and \"seeds\" for the point set metrics",
+            )
+    };
+    let args = App::new("Sample Testing")
         .version("0.123456789")
-        .about("")
-        .arg(clap::Arg::with_name("test").long("test"))
-        .arg(clap::Arg::with_name("search").long("search"))
-        .arg(clap::Arg::with_name("reference").long("ref"))
+        .about("Tools for testing and searching for Owen-scrambling hashes.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .arg(
-            clap::Arg::with_name("number")
-                .takes_value(true)
-                .required(false),
+            Arg::with_name("output_dir")
+                .long("output-dir")
+                .value_name("DIR")
+                .default_value(".")
+                .global(true)
+                .help("Directory to write output files to"),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Measures and prints the statistics of a hash")
+                .arg(hash_arg("fixed"))
+                .arg(number_arg("rounds", "10000000", "Number of stat rounds"))
+                .arg(seed_source_arg())
+                .arg(output_arg("output", "stats.png", "Stats image filename"))
+                .arg(
+                    Arg::with_name("no_image")
+                        .long("no-image")
+                        .help("Don't write any images"),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .help("Also write an svg image"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Randomly searches for better hashes")
+                .arg(number_arg("rounds", "10000", "Number of hashes to try"))
+                .arg(number_arg(
+                    "candidates",
+                    "4",
+                    "Number of top hashes to keep",
+                ))
+                .arg(number_arg(
+                    "stat-rounds",
+                    "4194304",
+                    "Number of stat rounds per hash",
+                ))
+                .arg(
+                    Arg::with_name("template")
+                        .long("template")
+                        .value_name("TEMPLATE")
+                        .default_value("mulxor ?; seedmix; mulxor ?; mulxor ?")
+                        .help(
+                            "Template for generating hashes, e.g. \"? ? ? ?\" for four random \
+                             ops.  See `HashTemplate` for the syntax",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("min-stat-rounds")
+                        .long("min-stat-rounds")
+                        .value_name("N")
+                        .help(
+                            "Enables successive halving, scoring hashes at this many stat \
+                             rounds first",
+                        ),
+                )
+                .arg(number_arg(
+                    "bracket-size",
+                    "256",
+                    "Number of hashes per successive halving bracket",
+                ))
+                .arg(number_arg(
+                    "eta",
+                    "4",
+                    "Successive halving promotion factor: the best 1/eta of each rung are \
+                     promoted to eta times the stat rounds",
+                ))
+                .arg(score_arg())
+                .arg(seed_source_arg())
+                .arg(Arg::with_name("pareto").long("pareto").help(
+                    "Keep the Pareto front of score vs. estimated cost, instead of the top hashes",
+                ))
+                .arg(
+                    Arg::with_name("cost-model")
+                        .long("cost-model")
+                        .value_name("COSTS")
+                        .default_value("")
+                        .help("Op costs for --pareto, e.g. \"add=1,xor=1,shift=1,mul=3,seed=1\""),
+                )
+                .arg(output_arg(
+                    "output-prefix",
+                    "candidate",
+                    "Filename prefix for the candidate stats images",
+                ))
+                .arg(
+                    Arg::with_name("no_image")
+                        .long("no-image")
+                        .help("Don't write any images"),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .help("Also write svg images"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("plot")
+                .about("Writes images of 2d scrambled point sets")
+                .arg(hash_arg("good"))
+                .arg(number_arg("count", "4", "Number of images (seeds)"))
+                .arg(number_arg("seed", "0", "First seed"))
+                .arg(number_arg(
+                    "resolution",
+                    "320",
+                    "Size in pixels of each plot",
+                ))
+                .arg(
+                    Arg::with_name("points")
+                        .long("points")
+                        .value_name("LIST")
+                        .default_value("256,1024,4096")
+                        .help("Comma-separated point counts, plotted left-to-right"),
+                )
+                .arg(output_arg(
+                    "output-prefix",
+                    "",
+                    "Filename prefix for the images",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("anim")
+                .about("Writes frames of points being added one power of two at a time")
+                .arg(hash_arg("good"))
+                .arg(number_arg(
+                    "max-power",
+                    "12",
+                    "Largest power of two to show",
+                ))
+                .arg(number_arg("seed", "0", "Seed"))
+                .arg(number_arg(
+                    "shuffle-seed",
+                    "1554099998",
+                    "Seed for the shuffled-index frames",
+                ))
+                .arg(number_arg(
+                    "resolution",
+                    "320",
+                    "Size in pixels of each frame",
+                ))
+                .arg(output_arg(
+                    "output-prefix",
+                    "anim",
+                    "Filename prefix for the frames",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("matrix")
+                .about("Writes an image of all 2d projections of the first N dimensions")
+                .arg(hash_arg("good"))
+                .arg(number_arg("dimensions", "8", "Number of dimensions"))
+                .arg(number_arg("points", "256", "Number of points"))
+                .arg(number_arg("seed", "0", "Seed"))
+                .arg(number_arg(
+                    "resolution",
+                    "128",
+                    "Size in pixels of each plot",
+                ))
+                .arg(output_arg(
+                    "output-prefix",
+                    "matrix",
+                    "Filename prefix for the image",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("enumerate")
+                .about("Exhaustively evaluates all small hash structures")
+                .arg(number_arg(
+                    "max-length",
+                    "2",
+                    "Maximum number of operations",
+                ))
+                .arg(
+                    Arg::with_name("shifts")
+                        .long("shifts")
+                        .value_name("LIST")
+                        .default_value("")
+                        .help("Comma-separated shift amounts to use [default: 1 through 31]"),
+                )
+                .arg(number_arg(
+                    "draws",
+                    "3",
+                    "Number of random constant draws to evaluate per structure",
+                ))
+                .arg(number_arg(
+                    "stat-rounds",
+                    "65536",
+                    "Number of stat rounds per draw",
+                ))
+                .arg(number_arg("top", "50", "Number of structures to print"))
+                .arg(score_arg())
+                .arg(output_arg(
+                    "output-prefix",
+                    "enumeration",
+                    "Filename prefix for the full ranked table",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Times the sampler and the scrambling hashes")
+                .arg(number_arg(
+                    "iterations",
+                    "67108864",
+                    "Number of calls to time per benchmark",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Writes a report comparing the quality and speed of several hashes")
+                .arg(
+                    Arg::with_name("hashes")
+                        .long("hashes")
+                        .value_name("LIST")
+                        .default_value("lk,v2,fast,good,fixed,ref")
+                        .help("Comma-separated names of the hashes to compare"),
+                )
+                .arg(number_arg(
+                    "stat-rounds",
+                    "4194304",
+                    "Number of stat rounds per hash",
+                ))
+                .arg(seed_source_arg())
+                .arg(number_arg(
+                    "timing-iterations",
+                    "4194304",
+                    "Number of hash calls to time per hash",
+                ))
+                .arg(
+                    Arg::with_name("points")
+                        .long("points")
+                        .value_name("LIST")
+                        .default_value("64,256,1024")
+                        .help("Comma-separated point counts to measure discrepancy at"),
+                )
+                .arg(number_arg(
+                    "seeds",
+                    "16",
+                    "Number of seeds to average discrepancy over",
+                ))
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .possible_values(&["md", "html"])
+                        .default_value("md")
+                        .help("Report format"),
+                )
+                .arg(output_arg(
+                    "output-prefix",
+                    "report",
+                    "Filename prefix for the report and its images",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("correlation")
+                .about("Measures how related the coordinates scrambled with related seeds are")
+                .arg(
+                    Arg::with_name("hashes")
+                        .long("hashes")
+                        .value_name("LIST")
+                        .default_value("lk,v2,fast,good,fixed,ref")
+                        .help("Comma-separated names of the hashes to test"),
+                )
+                .arg(number_arg(
+                    "points",
+                    "256",
+                    "Number of points per point set",
+                ))
+                .arg(number_arg(
+                    "seeds",
+                    "64",
+                    "Number of base seeds to average over",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("equiv")
+                .about("Checks whether two hashes compute the same function")
+                .arg(
+                    Arg::with_name("a")
+                        .value_name("HASH_A")
+                        .required(true)
+                        .help("A hash name (see --hash), or a template with all operands fixed"),
+                )
+                .arg(
+                    Arg::with_name("b")
+                        .value_name("HASH_B")
+                        .required(true)
+                        .help("The hash to compare against, in the same form"),
+                )
+                .arg(number_arg(
+                    "width",
+                    "10",
+                    "Bit width of the exhaustive checks",
+                ))
+                .arg(number_arg(
+                    "random-tests",
+                    "16777216",
+                    "Number of random 32-bit checks",
+                )),
         )
         .get_matches();
 
-    // Pick what to do based on command line arguments.
-    if args.is_present("test") {
-        let rounds = args
-            .value_of("number")
-            .unwrap_or("10000000")
-            .parse()
-            .unwrap();
-        do_test(rounds, true);
-    } else if args.is_present("search") {
-        let rounds = args.value_of("number").unwrap_or("10000").parse().unwrap();
-        do_hash_search(rounds, true);
-    } else {
-        let image_resolution = 320;
-        let image_count = args.value_of("number").unwrap_or("4").parse().unwrap();
-        let sample_function = if args.is_present("reference") {
-            |i, d, seed| sobol::sample_owen_reference(i, d, seed)
-        } else {
-            |i, d, seed| sobol::sample_owen_fast(i, d, seed)
-        };
+    let (subcommand, sub_args) = args.subcommand();
+    let sub_args = sub_args.unwrap();
+    let output_dir = Path::new(sub_args.value_of("output_dir").unwrap());
+    std::fs::create_dir_all(output_dir).unwrap();
 
-        for seed in 0..image_count {
-            let filename = if args.is_present("reference") {
-                format!("{:02}_ref.png", seed)
-            } else {
-                format!("{:02}.png", seed)
-            };
-            generate_samples_image(
-                sample_function,
-                image_resolution,
-                &[256, 1024, 4096],
-                seed,
-                &filename,
+    // Pick what to do based on command line arguments.
+    match subcommand {
+        "test" => {
+            // To try out a hand-written hash, use something like
+            // `FnScrambler(|n: u32, seed: u32| n.wrapping_add(seed))` here
+            // instead.
+            let (_, scrambler) = scrambler_from_args(sub_args);
+            let stats = measure_stats(
+                &*scrambler,
+                parse_arg(sub_args, "rounds"),
+                parse_arg(sub_args, "seed-source"),
+                true,
+                true,
             );
-        }
-    }
-}
-
-//=======================================================================
-// SUB-COMMANDS
-//=======================================================================
-
-/// Generates a bunch of 2d Owen-scrambled Sobol points, and writes them
-/// to an image.
-fn generate_samples_image<F>(
-    sample: F,
-    resolution: usize,
-    point_counts: &[u32], // A list of point-counts, which will be drawn sequentially in the image, left-to-right.
-    seed: u32,
-    image_path: &str,
-) where
-    F: Fn(u32, u32, u32) -> f32, // (sample_index, dimension, seed) -> coordinate
-{
-    const POINT_RADIUS: usize = 2;
-
-    let width = resolution * point_counts.len();
-    let height = resolution;
-    let mut image = vec![0xffu8; width * height * 4];
-
-    // Draws a point on the image.
-    let mut plot = |x: usize, y: usize| {
-        let min_x = x.saturating_sub(POINT_RADIUS);
-        let min_y = y.saturating_sub(POINT_RADIUS);
-        let max_x = (x + POINT_RADIUS + 1).min(width);
-        let max_y = (y + POINT_RADIUS + 1).min(height);
-
-        for yy in min_y..max_y {
-            for xx in min_x..max_x {
-                let x2 = x as isize - xx as isize;
-                let y2 = y as isize - yy as isize;
-                if (((x2 * x2) + (y2 * y2)) as f64).sqrt() <= POINT_RADIUS as f64 {
-                    image[(yy * width + xx) * 4] = 0x00;
-                    image[(yy * width + xx) * 4 + 1] = 0x00;
-                    image[(yy * width + xx) * 4 + 2] = 0x00;
-                    image[(yy * width + xx) * 4 + 3] = 0xFF;
+            print_stats(stats);
+            println!();
+
+            if !sub_args.is_present("no_image") {
+                let image_path = output_dir.join(sub_args.value_of("output").unwrap());
+                write_stats_image(stats, &mut File::create(&image_path).unwrap());
+                if sub_args.is_present("svg") {
+                    write_stats_svg(
+                        stats,
+                        &mut File::create(image_path.with_extension("svg")).unwrap(),
+                    );
                 }
             }
         }
-    };
 
-    // Plot the points at the various point counts.
-    for (set_idx, &point_count) in point_counts.iter().enumerate() {
-        for i in 0..point_count {
-            let x = sample(i, 0, seed);
-            let y = sample(i, 1, seed + 1);
-            plot(
-                (x * (resolution - 1) as f32) as usize + (resolution * set_idx),
-                (y * (resolution - 1) as f32) as usize,
+        "search" => {
+            let image_prefix = output_dir.join(sub_args.value_of("output-prefix").unwrap());
+            search_hashes(
+                &SearchConfig {
+                    rounds: parse_arg(sub_args, "rounds"),
+                    candidate_count: parse_arg(sub_args, "candidates"),
+                    stat_rounds: parse_arg(sub_args, "stat-rounds"),
+                    pareto: sub_args.is_present("pareto"),
+                    cost_model: parse_arg(sub_args, "cost-model"),
+                    score: parse_arg(sub_args, "score"),
+                    seed_source: parse_arg(sub_args, "seed-source"),
+                    template: parse_arg(sub_args, "template"),
+                    min_stat_rounds: sub_args
+                        .value_of("min-stat-rounds")
+                        .map(|n| parse_value("min-stat-rounds", n)),
+                    bracket_size: parse_arg(sub_args, "bracket-size"),
+                    eta: parse_arg(sub_args, "eta"),
+                },
+                Some(image_prefix.as_path()).filter(|_| !sub_args.is_present("no_image")),
+                sub_args.is_present("svg"),
             );
         }
-    }
-
-    let mut file = File::create(image_path).unwrap();
-    png_encode_mini::write_rgba_from_u8(&mut file, &image, width as u32, height as u32);
-}
-
-/// Tests the statistics of a hash, and prints the results to the console.
-/// Optionally writes a png image as well.
-fn do_test(rounds: u32, with_image: bool) {
-    let stats = measure_stats(
-        |n, seed| {
-            let mut n = n;
-            // // Reference Owen scramble implementation, performed on
-            // // reversed bits.
-            // n = n.reverse_bits();
-            // n = sobol::owen_scramble_reference_u32(n, seed);
-            // n = n.reverse_bits();
-
-            // // Original Laine-Karras hash.
-            // n = n.wrapping_add(seed);
-            // n ^= n.wrapping_mul(0x6c50b47c);
-            // n ^= n.wrapping_mul(0xb82f1e52);
-            // n ^= n.wrapping_mul(0xc7afe638);
-            // n ^= n.wrapping_mul(0x8d22f6e6);
-
-            // // Improved version 2.
-            // // From https://psychopath.io/post/2021_01_02_sobol_sampling_take_2
-            // n = n.wrapping_add(seed);
-            // n ^= 0xdc967795;
-            // n = n.wrapping_mul(0x97b754b7);
-            // n ^= 0x866350b1;
-            // n = n.wrapping_mul(0x9e3779cd);
-
-            // // From https://psychopath.io/post/2021_01_30_building_a_better_lk_hash
-            // // Old broken version.
-            // n *= 0x788aeeed;
-            // n ^= n * 0x41506a02;
-            // n += seed;
-            // n *= seed | 1;
-            // n ^= n * 0x7483dc64;
-
-            // // From https://psychopath.io/post/2021_01_30_building_a_better_lk_hash
-            // // Old fast broken version.
-            // n += n << 2;
-            // n ^= n * 0xfe9b5742;
-            // n += seed;
-            // n *= seed | 1;
-
-            // From https://psychopath.io/post/2021_01_30_building_a_better_lk_hash
-            // From the updated post, fixing the issue that Matt Pharr found.
-            n ^= n * 0x3d20adea;
-            n += seed;
-            n *= (seed >> 16) | 1;
-            n ^= n * 0x05526c56;
-            n ^= n * 0x53a22864;
-
-            // // Run a generated hash.  Note: a constant of zero in an op
-            // // indicates using the seed.
-            // n = exec_hash_slice(
-            //     &[HashOp::ShlAdd(2), HashOp::MulXor(2924493090), HashOp::SeedMix, ],
-            //     n,
-            //     seed,
-            // );
-
-            // // High quality reference.
-            // n = sobol::owen_scramble_reference_u32(n, seed);
 
-            n
-        },
-        rounds,
-        true,
-    );
-
-    // Print stats.
-    print_stats(stats);
-    println!();
-
-    // Write avalanche image.
-    if with_image {
-        write_stats_image(stats, &mut File::create("stats.png").unwrap());
-    }
-}
-
-/// Randomly searches for better hashes, and prints the result to console.
-/// Optionally also saves statistics png images of the top produced hashes.
-///
-/// All this does is generate hashes randomly, and keep the highest-scoring
-/// ones.  No fancy mutation approaches or whatnot, unfortunately.
-fn do_hash_search(rounds: usize, with_image: bool) {
-    use std::collections::HashMap;
-
-    const CANDIDATE_COUNT: usize = 4;
-    const STAT_ROUNDS: u32 = 1 << 22;
-
-    // Method to use to generate new hashes.
-    let generate = || {
-        // // Generate a totally random 5-op hash.
-        // [
-        //     HashOp::gen_random(),
-        //     HashOp::gen_random(),
-        //     HashOp::gen_random(),
-        //     HashOp::gen_random(),
-        // ]
-
-        // Start with an existing hash, and generate a new random
-        // constant for some of the operations.
-        [
-            HashOp::MulXor(123).new_constant(),
-            HashOp::SeedMix,
-            HashOp::MulXor(123).new_constant(),
-            HashOp::MulXor(123).new_constant(),
-        ]
-    };
+        "plot" => {
+            let (suffix, scrambler) = scrambler_from_args(sub_args);
+            let prefix = sub_args.value_of("output-prefix").unwrap();
+            let point_counts: Vec<u32> = parse_list_arg(sub_args, "points");
+            let first_seed: u32 = parse_arg(sub_args, "seed");
+            let count: u32 = parse_arg(sub_args, "count");
+
+            for seed in first_seed..(first_seed + count) {
+                generate_samples_image(
+                    &*scrambler,
+                    parse_arg(sub_args, "resolution"),
+                    &point_counts,
+                    seed,
+                    &output_dir.join(format!("{}{:02}{}.png", prefix, seed, suffix)),
+                );
+            }
+        }
 
-    //----------------
-    // Do actual optimization process.
-    //----------------
+        "anim" => {
+            let (suffix, scrambler) = scrambler_from_args(sub_args);
+            let prefix = sub_args.value_of("output-prefix").unwrap();
+
+            generate_samples_animation(
+                &*scrambler,
+                parse_arg(sub_args, "resolution"),
+                parse_arg(sub_args, "max-power"),
+                parse_arg(sub_args, "seed"),
+                None,
+                &output_dir.join(format!("{}{}", prefix, suffix)),
+            );
+            generate_samples_animation(
+                &*scrambler,
+                parse_arg(sub_args, "resolution"),
+                parse_arg(sub_args, "max-power"),
+                parse_arg(sub_args, "seed"),
+                Some(parse_arg(sub_args, "shuffle-seed")),
+                &output_dir.join(format!("{}_shuffled{}", prefix, suffix)),
+            );
+        }
 
-    let mut candidates: Vec<_> = (0..CANDIDATE_COUNT)
-        .map(|_| (generate(), std::f64::INFINITY, STATS_ZERO))
-        .collect();
-    let last_idx = candidates.len() - 1;
+        "matrix" => {
+            let (suffix, scrambler) = scrambler_from_args(sub_args);
+            let prefix = sub_args.value_of("output-prefix").unwrap();
+            let dimensions: u32 = parse_arg(sub_args, "dimensions");
+            if !(1..=sobol::MAX_DIMENSION).contains(&dimensions) {
+                eprintln!(
+                    "--dimensions must be between 1 and {}.",
+                    sobol::MAX_DIMENSION
+                );
+                std::process::exit(1);
+            }
 
-    println!();
-    for round in 0..rounds {
-        print!("\rround {}/{}", round, rounds);
-        std::io::stdout().flush();
+            generate_projection_matrix_image(
+                &*scrambler,
+                dimensions,
+                parse_arg(sub_args, "resolution"),
+                parse_arg(sub_args, "points"),
+                parse_arg(sub_args, "seed"),
+                &output_dir.join(format!("{}{}.png", prefix, suffix)),
+            );
+        }
 
-        // Generate and score a new hash.
-        let new_hash = generate();
-        let (stats, score) = {
-            let stats = measure_stats(
-                |n, seed| exec_hash_slice(&new_hash[..], n, seed),
-                STAT_ROUNDS,
-                false,
+        "enumerate" => {
+            let shifts: Vec<u32> = if sub_args.value_of("shifts") == Some("") {
+                (1..32).collect()
+            } else {
+                parse_list_arg(sub_args, "shifts")
+            };
+            rank_structures(
+                parse_arg(sub_args, "max-length"),
+                &shifts,
+                parse_arg(sub_args, "draws"),
+                parse_arg(sub_args, "stat-rounds"),
+                parse_arg(sub_args, "top"),
+                &parse_arg(sub_args, "score"),
+                &output_dir.join(format!(
+                    "{}.md",
+                    sub_args.value_of("output-prefix").unwrap()
+                )),
             );
-            (stats, score_stats(&stats))
-        };
+        }
 
-        // If it beats the current lowest-scoring hash, replace it.
-        if score < candidates[last_idx].1 {
-            candidates[last_idx] = (new_hash, score, stats);
-            candidates.sort_unstable_by(|x, y| x.1.partial_cmp(&y.1).unwrap());
+        "bench" => {
+            print_benchmarks(parse_arg(sub_args, "iterations"));
         }
-    }
-    println!();
 
-    // Print out the top hashes, and (optionally) write statistics png images
-    // for them as well.
-    for (i, c) in candidates.iter().enumerate() {
-        println!("Score: {}", c.1);
+        "compare" => {
+            let hashes = hashes_from_args(sub_args);
+            let config = CompareConfig {
+                stat_rounds: parse_arg(sub_args, "stat-rounds"),
+                timing_iterations: parse_arg(sub_args, "timing-iterations"),
+                point_counts: parse_list_arg(sub_args, "points"),
+                seeds: parse_arg(sub_args, "seeds"),
+                seed_source: parse_arg(sub_args, "seed-source"),
+                html: sub_args.value_of("format") == Some("html"),
+            };
+            write_comparison_report(
+                &hashes,
+                &config,
+                output_dir,
+                sub_args.value_of("output-prefix").unwrap(),
+            );
+        }
 
-        print!("&[");
-        for p in c.0.iter() {
-            print!("HashOp::{:?}, ", *p);
+        "correlation" => {
+            print_correlation_table(
+                &hashes_from_args(sub_args),
+                parse_arg(sub_args, "points"),
+                parse_arg(sub_args, "seeds"),
+            );
         }
-        println!("]");
-        print_stats(c.2);
-        println!();
 
-        if with_image {
-            write_stats_image(
-                c.2,
-                &mut File::create(&format!("candidate_{:02}.png", i + 1)).unwrap(),
+        "equiv" => {
+            let a = scrambler_from_spec(sub_args.value_of("a").unwrap());
+            let b = scrambler_from_spec(sub_args.value_of("b").unwrap());
+            let counterexample = find_counterexample(
+                &*a,
+                &*b,
+                parse_arg(sub_args, "width"),
+                parse_arg(sub_args, "random-tests"),
             );
+            if let Some((x, seed)) = counterexample {
+                println!(
+                    "Different: x = {:#010x}, seed = {:#010x} gives {:#010x} vs. {:#010x}",
+                    x,
+                    seed,
+                    a.hash(x, seed),
+                    b.hash(x, seed)
+                );
+                std::process::exit(1);
+            }
+            println!("Equivalent (no counterexample found).");
         }
+
+        _ => unreachable!(),
     }
 }
 
-//=======================================================================
-// UTILS
-//=======================================================================
-
-fn hash_u32(n: u32, seed: u32) -> u32 {
-    // Seeding.
-    let mut n = 0x6217c6e1 ^ n.wrapping_add(seed.wrapping_mul(0x9e3779b9));
-
-    // From https://github.com/skeeto/hash-prospector
-    n ^= n >> 17;
-    n = n.wrapping_mul(0xed5ad4bb);
-    n ^= n >> 11;
-    n = n.wrapping_mul(0xac4c1b51);
-    n ^= n >> 15;
-    n = n.wrapping_mul(0x31848bab);
-    n ^= n >> 14;
+/// Picks the scrambler to use based on the `--hash` command line argument.
+///
+/// Also returns a suffix for output filenames, which is empty if the
+/// argument was left at its default.
+fn scrambler_from_args(args: &clap::ArgMatches) -> (String, Box<dyn OwenScrambler>) {
+    let name = args.value_of("hash").unwrap();
+    let scrambler = scrambler_from_name(name);
+    let suffix = if args.occurrences_of("hash") == 0 {
+        String::new()
+    } else {
+        format!("_{}", name)
+    };
 
-    n
+    (suffix, scrambler)
 }
 
-/// Scores the given hash statistics.  Used for searching for better hashes.
-///
-/// Lower score is better (like golf!).
-fn score_stats(stats: &Stats) -> f64 {
-    let mut score = 0.0;
+/// Looks up the scramblers named by the comma-separated `--hashes` command
+/// line argument, paired with their names.
+fn hashes_from_args(args: &clap::ArgMatches) -> Vec<(String, Box<dyn OwenScrambler>)> {
+    parse_list_arg::<String>(args, "hashes")
+        .into_iter()
+        .map(|name| {
+            let scrambler = scrambler_from_name(&name);
+            (name, scrambler)
+        })
+        .collect()
+}
 
-    // Avalanche metric.
-    for bit_out in 0..32 {
-        for bit_in in 0..bit_out {
-            let diff = stats.avalanche[bit_in][bit_out] - 0.5;
-            score += diff * diff;
-        }
+/// Looks up a scrambler by name: any `LkHash` name, "ref" for the reference
+/// scramble, or "raw" for no scrambling.  Exits with an error message if
+/// there's no such scrambler.
+fn scrambler_from_name(name: &str) -> Box<dyn OwenScrambler> {
+    match name {
+        "ref" => Box::new(Reference),
+        "raw" => Box::new(Unscrambled),
+        _ => Box::new(LkHash::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown hash \"{}\".", name);
+            std::process::exit(1);
+        })),
     }
+}
 
-    // Avalanche bias metric, trying to match the expected bias of a
-    // proper full Owen scramble.  The first sixteen values here were computed
-    // analytically, and the remaining were approximated following a strong
-    // trend in the values by that point, and should be "reasonably" accurate.
-    const TARGET_BIAS: [f64; 32] = [
-        0.0, 1.0, 0.5, 0.375, 0.273437, 0.19638, 0.139949, 0.099346, 0.070386, 0.049819, 0.035244,
-        0.024927, 0.017628, 0.012466, 0.008815, 0.006233, 0.004407, 0.003117, 0.002204, 0.001558,
-        0.001102, 0.000779, 0.000551, 0.000390, 0.000275, 0.000195, 0.000138, 0.000097, 0.000069,
-        0.000049, 0.000034, 0.000024,
-    ];
-    for bit_out in 0..32 {
-        for bit_in in 0..bit_out {
-            let diff = stats.avalanche_avg_bias[bit_in][bit_out] - TARGET_BIAS[bit_out];
-            score += diff * diff;
-        }
+/// Like `scrambler_from_name()`, but also accepts a `HashTemplate` with
+/// every operand fixed (e.g. "mul 0x788aeeed; add seed; mul seed") as an
+/// explicit `HashOp` sequence.  Exits with an error message if any operand
+/// isn't fixed.
+fn scrambler_from_spec(spec: &str) -> Box<dyn OwenScrambler> {
+    if spec == "ref" || spec == "raw" || LkHash::from_name(spec).is_some() {
+        return scrambler_from_name(spec);
     }
+    let template: HashTemplate = parse_value("hash", spec);
+    let ops = template.fixed_ops().unwrap_or_else(|e| {
+        eprintln!("Invalid hash \"{}\": {}", spec, e);
+        std::process::exit(1);
+    });
+    Box::new(FnScrambler(move |x, seed| exec_hash_slice(&ops, x, seed)))
+}
 
-    // // Tree bias metric
-    // // With the new seed mixing op, this is unnecessary to target since
-    // // it's pretty much always perfect.
-    // for x in 0..32 {
-    //     for y in (x + 1)..32 {
-    //         let diff = (stats.tree_bias[x][y] - 0.5) * 2.0;
-    //         score += diff * diff;
-    //     }
-    // }
+/// Parses the value of a command line argument, exiting with an error
+/// message if it's invalid.
+fn parse_arg<T>(args: &clap::ArgMatches, name: &str) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    parse_value(name, args.value_of(name).unwrap())
+}
 
-    score
+/// Like `parse_arg()`, but for comma-separated lists.
+fn parse_list_arg<T>(args: &clap::ArgMatches, name: &str) -> Vec<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    args.value_of(name)
+        .unwrap()
+        .split(',')
+        .map(|v| parse_value(name, v.trim()))
+        .collect()
+}
+
+fn parse_value<T>(name: &str, value: &str) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().unwrap_or_else(|e| {
+        eprintln!("Invalid value \"{}\" for --{}: {}", value, name, e);
+        std::process::exit(1);
+    })
 }
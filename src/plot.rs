@@ -0,0 +1,295 @@
+//! Simple drawing utilities for writing annotated images.
+//!
+//! Drawing is done through the `Canvas` trait, so the same layout code can
+//! produce both png (via `RasterCanvas`) and svg (via `SvgCanvas`) output.
+//! All coordinates are in pixels, with the origin at the top-left.
+
+use std::fs::File;
+use std::io::Write;
+
+/// Height in pixels of text drawn with a scale of 1.
+pub const TEXT_HEIGHT: usize = 7;
+
+/// Width in pixels of a single character drawn with a scale of 1,
+/// including spacing.
+pub const CHAR_WIDTH: usize = 6;
+
+pub trait Canvas {
+    /// Fills a rectangle with a solid color.
+    fn rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]);
+
+    /// Draws a single line of text, with `(x, y)` as its top-left corner.
+    fn text(&mut self, x: usize, y: usize, scale: usize, text: &str, color: [u8; 3]);
+}
+
+/// Returns the width in pixels of `text` drawn at the given scale.
+pub fn text_width(text: &str, scale: usize) -> usize {
+    (text.chars().count() * CHAR_WIDTH).saturating_sub(1) * scale
+}
+
+//----------------------------------------------------------------------
+
+/// A `Canvas` that draws into an rgb pixel buffer.
+pub struct RasterCanvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>, // Rgba, top row first.
+}
+
+impl RasterCanvas {
+    pub fn new(width: usize, height: usize, background: [u8; 3]) -> RasterCanvas {
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for _ in 0..(width * height) {
+            pixels.extend_from_slice(&[background[0], background[1], background[2], 0xFF]);
+        }
+        RasterCanvas {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn write_png(&self, file: &mut File) {
+        // `png_encode_mini` writes the last row of the buffer at the top of
+        // the image, so flip the rows to keep our top-left origin.
+        let row_size = self.width * 4;
+        let mut flipped = Vec::with_capacity(self.pixels.len());
+        for row in self.pixels.chunks(row_size).rev() {
+            flipped.extend_from_slice(row);
+        }
+        png_encode_mini::write_rgba_from_u8(file, &flipped, self.width as u32, self.height as u32)
+            .unwrap();
+    }
+}
+
+impl Canvas for RasterCanvas {
+    fn rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]) {
+        let max_x = (x + w).min(self.width);
+        let max_y = (y + h).min(self.height);
+        for yy in y..max_y {
+            for xx in x..max_x {
+                let i = (yy * self.width + xx) * 4;
+                self.pixels[i..(i + 3)].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, scale: usize, text: &str, color: [u8; 3]) {
+        for (char_idx, c) in text.chars().enumerate() {
+            let glyph = glyph(c);
+            let char_x = x + char_idx * CHAR_WIDTH * scale;
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..5 {
+                    if (bits >> (4 - col)) & 1 != 0 {
+                        self.rect(char_x + col * scale, y + row * scale, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// A `Canvas` that builds an svg document.
+pub struct SvgCanvas {
+    width: usize,
+    height: usize,
+    body: String,
+}
+
+impl SvgCanvas {
+    pub fn new(width: usize, height: usize, background: [u8; 3]) -> SvgCanvas {
+        let mut canvas = SvgCanvas {
+            width,
+            height,
+            body: String::new(),
+        };
+        canvas.rect(0, 0, width, height, background);
+        canvas
+    }
+
+    pub fn write_svg(&self, file: &mut File) {
+        write!(
+            file,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\" shape-rendering=\"crispEdges\">\n{2}</svg>\n",
+            self.width, self.height, self.body,
+        )
+        .unwrap();
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]) {
+        self.body.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>\n",
+            x, y, w, h, color[0], color[1], color[2],
+        ));
+    }
+
+    fn text(&mut self, x: usize, y: usize, scale: usize, text: &str, color: [u8; 3]) {
+        let text = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        self.body.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" \
+             fill=\"#{:02x}{:02x}{:02x}\">{}</text>\n",
+            x,
+            y + TEXT_HEIGHT * scale,
+            (TEXT_HEIGHT + 3) * scale,
+            color[0],
+            color[1],
+            color[2],
+            text,
+        ));
+    }
+}
+
+//----------------------------------------------------------------------
+// Colormaps.
+
+/// Perceptually uniform sequential colormap (matplotlib's "viridis").
+///
+/// `t` is clamped to [0.0, 1.0].
+pub fn colormap_viridis(t: f64) -> [u8; 3] {
+    const STOPS: [[u8; 3]; 9] = [
+        [68, 1, 84],
+        [71, 44, 122],
+        [59, 81, 139],
+        [44, 113, 142],
+        [33, 144, 141],
+        [39, 173, 129],
+        [92, 200, 99],
+        [170, 220, 50],
+        [253, 231, 37],
+    ];
+    lerp_stops(&STOPS, t)
+}
+
+/// Diverging colormap, blue for negative and red for positive (ColorBrewer's
+/// "RdBu", reversed).
+///
+/// `t` is clamped to [-1.0, 1.0], with zero mapping to near-white.
+pub fn colormap_diverging(t: f64) -> [u8; 3] {
+    const STOPS: [[u8; 3]; 9] = [
+        [33, 102, 172],
+        [67, 147, 195],
+        [146, 197, 222],
+        [209, 229, 240],
+        [247, 247, 247],
+        [253, 219, 199],
+        [244, 165, 130],
+        [214, 96, 77],
+        [178, 24, 43],
+    ];
+    lerp_stops(&STOPS, (t + 1.0) * 0.5)
+}
+
+fn lerp_stops(stops: &[[u8; 3]], t: f64) -> [u8; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let pos = t * (stops.len() - 1) as f64;
+    let i = (pos as usize).min(stops.len() - 2);
+    let alpha = pos - i as f64;
+    let mut color = [0u8; 3];
+    for c in 0..3 {
+        let a = stops[i][c] as f64;
+        let b = stops[i + 1][c] as f64;
+        color[c] = (a + (b - a) * alpha).round() as u8;
+    }
+    color
+}
+
+//----------------------------------------------------------------------
+// Rgba buffers.
+
+/// Draws a filled circle of the given radius and rgb color into an rgba
+/// image buffer.
+pub fn plot_point(
+    image: &mut [u8],
+    (width, height): (usize, usize),
+    x: usize,
+    y: usize,
+    radius: usize,
+    color: [u8; 3],
+) {
+    let min_x = x.saturating_sub(radius);
+    let min_y = y.saturating_sub(radius);
+    let max_x = (x + radius + 1).min(width);
+    let max_y = (y + radius + 1).min(height);
+
+    for yy in min_y..max_y {
+        for xx in min_x..max_x {
+            let x2 = x as isize - xx as isize;
+            let y2 = y as isize - yy as isize;
+            if (((x2 * x2) + (y2 * y2)) as f64).sqrt() <= radius as f64 {
+                let i = (yy * width + xx) * 4;
+                image[i..(i + 3)].copy_from_slice(&color);
+                image[i + 3] = 0xFF;
+            }
+        }
+    }
+}
+
+//----------------------------------------------------------------------
+// Font.
+
+/// Returns the 5x7 bitmap of a character.  Each entry is one row, top row
+/// first, with the leftmost pixel in bit 4.
+///
+/// Lower-case letters are drawn as upper-case, and unsupported characters
+/// are drawn as a hollow box.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F],
+    }
+}
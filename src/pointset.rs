@@ -0,0 +1,415 @@
+//! Quality metrics and images for point sets.
+
+use std::fs::File;
+use std::path::Path;
+
+use crate::hash_combine;
+use crate::plot::plot_point;
+use crate::scramble::OwenScrambler;
+use crate::sobol;
+
+/// Computes the L2-star discrepancy of a point set in [0, 1)^D, using
+/// Warnock's formula.
+///
+/// This is O(N^2) in the number of points, so is only really practical for
+/// up to a few thousand points.
+pub fn l2_star_discrepancy<const D: usize>(points: &[[f64; D]]) -> f64 {
+    let n = points.len() as f64;
+
+    let mut sum_1 = 0.0;
+    for p in points.iter() {
+        sum_1 += p.iter().map(|x| 1.0 - x * x).product::<f64>();
+    }
+
+    let mut sum_2 = 0.0;
+    for a in points.iter() {
+        for b in points.iter() {
+            sum_2 += a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| 1.0 - x.max(*y))
+                .product::<f64>();
+        }
+    }
+
+    let squared =
+        3.0f64.powi(-(D as i32)) - (2.0f64.powi(1 - D as i32) / n) * sum_1 + sum_2 / (n * n);
+    squared.max(0.0).sqrt()
+}
+
+/// The average L2-star discrepancy of the first `point_count` points of
+/// Sobol dimensions 0 and 1, Owen scrambled with the given scrambler, over
+/// `seeds` different seeds.
+pub fn mean_discrepancy<S>(scrambler: &S, point_count: u32, seeds: u32) -> f64
+where
+    S: OwenScrambler + ?Sized,
+{
+    let total: f64 = (0..seeds)
+        .map(|seed| l2_star_discrepancy(&scrambled_points(scrambler, point_count, seed)))
+        .sum();
+    total / seeds.max(1) as f64
+}
+
+/// The variance, over `seeds` different seeds, of the estimate of the area
+/// of a quarter disk, integrated with the same points as
+/// `mean_discrepancy()`.
+///
+/// The result is relative to the variance of plain independent random
+/// sampling with the same number of points, so lower is better and 1.0
+/// means "no better than random".
+pub fn integration_variance<S>(scrambler: &S, point_count: u32, seeds: u32) -> f64
+where
+    S: OwenScrambler + ?Sized,
+{
+    let area = std::f64::consts::FRAC_PI_4;
+    let estimates: Vec<f64> = (0..seeds)
+        .map(|seed| {
+            let points = scrambled_points(scrambler, point_count, seed);
+            let inside = points
+                .iter()
+                .filter(|p| p[0] * p[0] + p[1] * p[1] < 1.0)
+                .count();
+            inside as f64 / point_count.max(1) as f64
+        })
+        .collect();
+
+    let variance = estimates
+        .iter()
+        .map(|e| (e - area) * (e - area))
+        .sum::<f64>()
+        / estimates.len().max(1) as f64;
+    let random_variance = area * (1.0 - area) / point_count.max(1) as f64;
+    variance / random_variance
+}
+
+//----------------------------------------------------------------------
+
+/// How the seeds of the two coordinates of a 2d point set are derived from
+/// a single base seed, for `related_seed_correlation()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeedDerivation {
+    /// `seed + n` for the nth coordinate, passed to the scrambler as-is.
+    /// This is what the point set images do, except for the randomization.
+    Offset,
+
+    /// `hash_combine(seed, n)` for the nth coordinate, passed to the
+    /// scrambler as-is, as in Burley's "Practical Hash-based Owen
+    /// Scrambling".
+    HashCombine,
+
+    /// `seed + n`, but randomized as by `sobol::sample_owen()`.
+    Randomized,
+}
+
+impl SeedDerivation {
+    pub const ALL: &'static [SeedDerivation] = &[
+        SeedDerivation::Offset,
+        SeedDerivation::HashCombine,
+        SeedDerivation::Randomized,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SeedDerivation::Offset => "seed + n",
+            SeedDerivation::HashCombine => "hash_combine(seed, n)",
+            SeedDerivation::Randomized => "randomized seed + n",
+        }
+    }
+}
+
+/// The results of `related_seed_correlation()`.
+#[derive(Debug, Copy, Clone)]
+pub struct Correlation {
+    pub correlation: f64, // Mean absolute Pearson correlation of the coordinates.
+    pub discrepancy: f64, // Mean L2-star discrepancy of the 2d points.
+}
+
+/// Measures how related the two coordinates of a 2d point set are when
+/// they're scrambled with related seeds.
+///
+/// The x and y coordinates are Sobol dimensions `dimensions.0` and
+/// `dimensions.1` (which may be the same dimension), scrambled with the
+/// first and second seeds derived from each base seed in `[0, seeds)`.
+/// The results are averaged over the base seeds.
+///
+/// For comparison, two independent uniform random streams have an expected
+/// absolute correlation of about `sqrt(2 / (pi * point_count))`.
+pub fn related_seed_correlation<S>(
+    scrambler: &S,
+    derivation: SeedDerivation,
+    dimensions: (u32, u32),
+    point_count: u32,
+    seeds: u32,
+) -> Correlation
+where
+    S: OwenScrambler + ?Sized,
+{
+    let coordinate = |i: u32, dimension: u32, seed: u32, n: u32| -> f64 {
+        match derivation {
+            SeedDerivation::Offset => {
+                let x = scrambler.scramble(sobol::sobol_u32(i, dimension), seed.wrapping_add(n));
+                x as f64 / (1u64 << 32) as f64
+            }
+            SeedDerivation::HashCombine => {
+                let x = scrambler.scramble(sobol::sobol_u32(i, dimension), hash_combine(seed, n));
+                x as f64 / (1u64 << 32) as f64
+            }
+            SeedDerivation::Randomized => {
+                sobol::sample_owen(scrambler, i, dimension, seed.wrapping_add(n)) as f64
+            }
+        }
+    };
+
+    let mut total = Correlation {
+        correlation: 0.0,
+        discrepancy: 0.0,
+    };
+    for seed in 0..seeds {
+        let points: Vec<[f64; 2]> = (0..point_count)
+            .map(|i| {
+                [
+                    coordinate(i, dimensions.0, seed, 0),
+                    coordinate(i, dimensions.1, seed, 1),
+                ]
+            })
+            .collect();
+        total.correlation += pearson_correlation(&points).abs();
+        total.discrepancy += l2_star_discrepancy(&points);
+    }
+
+    let n = seeds.max(1) as f64;
+    Correlation {
+        correlation: total.correlation / n,
+        discrepancy: total.discrepancy / n,
+    }
+}
+
+/// The Pearson correlation coefficient of the x and y coordinates of a 2d
+/// point set.
+pub fn pearson_correlation(points: &[[f64; 2]]) -> f64 {
+    let n = points.len().max(1) as f64;
+    let mean_x = points.iter().map(|p| p[0]).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p[1]).sum::<f64>() / n;
+
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for p in points.iter() {
+        let (dx, dy) = (p[0] - mean_x, p[1] - mean_y);
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+
+    if var_x == 0.0 || var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
+//----------------------------------------------------------------------
+// Images.
+
+/// Generates a bunch of 2d Owen-scrambled Sobol points, and writes them
+/// to an image.
+pub fn generate_samples_image<S>(
+    scrambler: &S,
+    resolution: usize,
+    point_counts: &[u32], // A list of point-counts, which will be drawn sequentially in the image, left-to-right.
+    seed: u32,
+    image_path: &Path,
+) where
+    S: OwenScrambler + ?Sized,
+{
+    const POINT_RADIUS: usize = 2;
+
+    let width = resolution * point_counts.len();
+    let height = resolution;
+    let mut image = vec![0xffu8; width * height * 4];
+
+    // Plot the points at the various point counts.
+    for (set_idx, &point_count) in point_counts.iter().enumerate() {
+        for i in 0..point_count {
+            let x = sobol::sample_owen(scrambler, i, 0, seed);
+            let y = sobol::sample_owen(scrambler, i, 1, seed + 1);
+            plot_point(
+                &mut image,
+                (width, height),
+                (x * (resolution - 1) as f32) as usize + (resolution * set_idx),
+                (y * (resolution - 1) as f32) as usize,
+                POINT_RADIUS,
+                [0x00, 0x00, 0x00],
+            );
+        }
+    }
+
+    let mut file = File::create(image_path).unwrap();
+    png_encode_mini::write_rgba_from_u8(&mut file, &image, width as u32, height as u32).unwrap();
+}
+
+/// Generates a sequence of png frames showing 2d Owen-scrambled Sobol
+/// points being added one power of two at a time.
+///
+/// Frame `k` shows the first `2^k` points, with the points added since the
+/// previous frame drawn in red.  Each frame also has a faint grid of `2^k`
+/// equal cells overlaid, so you can see how well that prefix stratifies.
+/// Frames are written to `{path_prefix}_{k:02}.png`.
+pub fn generate_samples_animation<S>(
+    scrambler: &S,
+    resolution: usize,
+    max_power: u32, // Frames are generated for point counts 2^0 through 2^max_power.
+    seed: u32,
+    shuffle_seed: Option<u32>, // If given, the sample indices are shuffled with this seed.
+    path_prefix: &Path,
+) where
+    S: OwenScrambler + ?Sized,
+{
+    const POINT_RADIUS: usize = 2;
+
+    let sample = |i, d, seed| match shuffle_seed {
+        Some(shuffle_seed) => sobol::sample_owen_shuffled(scrambler, i, d, shuffle_seed, seed),
+        None => sobol::sample_owen(scrambler, i, d, seed),
+    };
+
+    for power in 0..=max_power {
+        let mut image = vec![0xffu8; resolution * resolution * 4];
+
+        // Stratification grid.  Odd powers get more columns than rows.
+        let columns = 1usize << power.div_ceil(2);
+        let rows = 1usize << (power / 2);
+        let is_boundary = |p: usize, cells: usize| {
+            p > 0 && (p * cells / resolution) != ((p - 1) * cells / resolution)
+        };
+        for y in 0..resolution {
+            for x in 0..resolution {
+                if is_boundary(x, columns) || is_boundary(y, rows) {
+                    let i = (y * resolution + x) * 4;
+                    image[i..(i + 3)].copy_from_slice(&[0xc0, 0xc0, 0xff]);
+                }
+            }
+        }
+
+        // Plot the points, with the most recently added half in red.
+        let point_count = 1u32 << power;
+        for i in 0..point_count {
+            let x = sample(i, 0, seed);
+            let y = sample(i, 1, seed + 1);
+            let color = if i >= point_count / 2 && power > 0 {
+                [0xff, 0x00, 0x00]
+            } else {
+                [0x00, 0x00, 0x00]
+            };
+            plot_point(
+                &mut image,
+                (resolution, resolution),
+                (x * (resolution - 1) as f32) as usize,
+                (y * (resolution - 1) as f32) as usize,
+                POINT_RADIUS,
+                color,
+            );
+        }
+
+        let mut file = File::create(format!("{}_{:02}.png", path_prefix.display(), power)).unwrap();
+        png_encode_mini::write_rgba_from_u8(
+            &mut file,
+            &image,
+            resolution as u32,
+            resolution as u32,
+        )
+        .unwrap();
+    }
+}
+
+/// Generates a grid of 2d projections of Owen-scrambled Sobol points, and
+/// writes it to an image.
+///
+/// The cell in row `i` (counting up from the bottom) and column `j` plots
+/// dimension `j` against dimension `i`, for all dimensions in
+/// `0..dimensions`.  Each dimension `d` is scrambled with `seed + d`,
+/// matching `generate_samples_image()`.  The diagonal cells are left gray.
+/// This is useful for spotting badly correlated pairs of dimensions, both in
+/// the unscrambled direction numbers and in the scrambles.
+pub fn generate_projection_matrix_image<S>(
+    scrambler: &S,
+    dimensions: u32,
+    cell_resolution: usize,
+    point_count: u32,
+    seed: u32,
+    image_path: &Path,
+) where
+    S: OwenScrambler + ?Sized,
+{
+    const POINT_RADIUS: usize = 1;
+    const GAP: usize = 4;
+
+    assert!((1..=sobol::MAX_DIMENSION).contains(&dimensions));
+    let dims = dimensions as usize;
+    let cell_stride = cell_resolution + GAP;
+    let width = cell_stride * dims - GAP;
+    let height = width;
+    let mut image = vec![0x60u8; width * height * 4];
+
+    // Compute all the point coordinates up-front, since each is used many
+    // times.
+    let coords: Vec<Vec<f32>> = (0..dimensions)
+        .map(|d| {
+            (0..point_count)
+                .map(|i| sobol::sample_owen(scrambler, i, d, seed + d))
+                .collect()
+        })
+        .collect();
+
+    for row in 0..dims {
+        for col in 0..dims {
+            let min_x = col * cell_stride;
+            let min_y = row * cell_stride;
+
+            // Cell background.
+            let background = if row == col { 0xb0 } else { 0xff };
+            for y in min_y..(min_y + cell_resolution) {
+                for x in min_x..(min_x + cell_resolution) {
+                    let i = (y * width + x) * 4;
+                    image[i..(i + 3)].copy_from_slice(&[background; 3]);
+                    image[i + 3] = 0xFF;
+                }
+            }
+            if row == col {
+                continue;
+            }
+
+            // Points.
+            for (&x, &y) in coords[col].iter().zip(coords[row].iter()) {
+                plot_point(
+                    &mut image,
+                    (width, height),
+                    (x * (cell_resolution - 1) as f32) as usize + min_x,
+                    (y * (cell_resolution - 1) as f32) as usize + min_y,
+                    POINT_RADIUS,
+                    [0x00, 0x00, 0x00],
+                );
+            }
+        }
+    }
+
+    let mut file = File::create(image_path).unwrap();
+    png_encode_mini::write_rgba_from_u8(&mut file, &image, width as u32, height as u32).unwrap();
+}
+
+//----------------------------------------------------------------------
+
+/// The scrambled 2d point set used by `mean_discrepancy()` and
+/// `integration_variance()`.  Seeded the same way as the point set images.
+fn scrambled_points<S>(scrambler: &S, point_count: u32, seed: u32) -> Vec<[f64; 2]>
+where
+    S: OwenScrambler + ?Sized,
+{
+    (0..point_count)
+        .map(|i| {
+            [
+                sobol::sample_owen(scrambler, i, 0, seed) as f64,
+                sobol::sample_owen(scrambler, i, 1, seed + 1) as f64,
+            ]
+        })
+        .collect()
+}
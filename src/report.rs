@@ -0,0 +1,220 @@
+//! Reports comparing several scrambling hashes.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::bench::{ns_per_hash, ns_per_hash_batched};
+use crate::pointset::{
+    generate_samples_image, mean_discrepancy, related_seed_correlation, SeedDerivation,
+};
+use crate::scramble::OwenScrambler;
+use crate::stats::{average_bias, measure_stats, score_stats, write_stats_image, SeedSource};
+
+/// Settings for `write_comparison_report()`.
+pub struct CompareConfig {
+    pub stat_rounds: u32,        // Rounds of `measure_stats()` per hash.
+    pub timing_iterations: u32,  // Hash calls to time per hash.
+    pub point_counts: Vec<u32>,  // Point counts to measure discrepancy at.
+    pub seeds: u32,              // Number of seeds to average discrepancy over.
+    pub seed_source: SeedSource, // Seeds to measure statistics with.
+    pub html: bool,              // Write an html report instead of markdown.
+}
+
+/// Measures the statistics, score, speed, and point set discrepancy of each
+/// of the named hashes, and writes a single report comparing them.
+///
+/// The report is written to `{prefix}.md` (or `.html`) in `output_dir`,
+/// along with a stats image and a point set image for each hash, which the
+/// report embeds.
+pub fn write_comparison_report(
+    hashes: &[(String, Box<dyn OwenScrambler>)],
+    config: &CompareConfig,
+    output_dir: &Path,
+    prefix: &str,
+) {
+    struct Row {
+        name: String,
+        score: f64,
+        avg_bias: f64,
+        ns_per_hash: f64,
+        ns_per_hash_batched: f64,
+        discrepancy: Vec<f64>,
+        stats_image: String,
+        points_image: String,
+    }
+
+    let mut rows = Vec::new();
+    for (name, scrambler) in hashes.iter() {
+        println!("Measuring \"{}\"...", name);
+        let scrambler = &**scrambler;
+
+        let stats = measure_stats(
+            scrambler,
+            config.stat_rounds,
+            config.seed_source,
+            true,
+            true,
+        );
+        let ns_per_hash_batched = ns_per_hash_batched(scrambler, config.timing_iterations);
+        let ns_per_hash = ns_per_hash(scrambler, config.timing_iterations);
+
+        // Discrepancy of dimensions 0 and 1, seeded the same way as the
+        // point set images.
+        let discrepancy = config
+            .point_counts
+            .iter()
+            .map(|&point_count| mean_discrepancy(scrambler, point_count, config.seeds))
+            .collect();
+
+        let stats_image = format!("{}_{}_stats.png", prefix, name);
+        let points_image = format!("{}_{}_points.png", prefix, name);
+        write_stats_image(
+            stats,
+            &mut File::create(output_dir.join(&stats_image)).unwrap(),
+        );
+        generate_samples_image(
+            scrambler,
+            256,
+            &config.point_counts,
+            0,
+            &output_dir.join(&points_image),
+        );
+
+        rows.push(Row {
+            name: name.clone(),
+            score: score_stats(&stats),
+            avg_bias: average_bias(&stats),
+            ns_per_hash,
+            ns_per_hash_batched,
+            discrepancy,
+            stats_image,
+            points_image,
+        });
+    }
+
+    // Build the report.
+    let description = format!(
+        "Stats measured with {} rounds per hash.  Times are per call to the \
+         hash, not including the bit reversals, both one-at-a-time (latency) \
+         and batched (throughput).  Discrepancy is \
+         the L2-star discrepancy of dimensions 0 and 1, averaged over {} seeds.",
+        config.stat_rounds, config.seeds
+    );
+    let mut header = vec![
+        "Hash".to_string(),
+        "Score".to_string(),
+        "Avg bias".to_string(),
+        "ns/hash".to_string(),
+        "ns/hash (batched)".to_string(),
+    ];
+    for point_count in config.point_counts.iter() {
+        header.push(format!("Discrepancy ({})", point_count));
+    }
+    let table: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![
+                row.name.clone(),
+                format!("{:.5}", row.score),
+                format!("{:.4}", row.avg_bias),
+                format!("{:.2}", row.ns_per_hash),
+                format!("{:.2}", row.ns_per_hash_batched),
+            ];
+            for d in row.discrepancy.iter() {
+                cells.push(format!("{:.6}", d));
+            }
+            cells
+        })
+        .collect();
+
+    let mut report = String::new();
+    if config.html {
+        report.push_str("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\">");
+        report.push_str("<title>Hash comparison</title></head>\n<body>\n");
+        report.push_str("<h1>Hash comparison</h1>\n");
+        report.push_str(&format!("<p>{}</p>\n<table>\n", description));
+        report.push_str(&format!("<tr><th>{}</th></tr>\n", header.join("</th><th>")));
+        for cells in table.iter() {
+            report.push_str(&format!("<tr><td>{}</td></tr>\n", cells.join("</td><td>")));
+        }
+        report.push_str("</table>\n");
+        for row in rows.iter() {
+            report.push_str(&format!(
+                "<h2>{0}</h2>\n<p><img src=\"{1}\" alt=\"{0} stats\"></p>\n\
+                 <p><img src=\"{2}\" alt=\"{0} points\"></p>\n",
+                row.name, row.stats_image, row.points_image
+            ));
+        }
+        report.push_str("</body>\n</html>\n");
+    } else {
+        report.push_str("# Hash comparison\n\n");
+        report.push_str(&format!("{}\n\n", description));
+        report.push_str(&format!("| {} |\n", header.join(" | ")));
+        report.push_str(&format!("|---|{}\n", "---:|".repeat(header.len() - 1)));
+        for cells in table.iter() {
+            report.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        for row in rows.iter() {
+            report.push_str(&format!(
+                "\n## {0}\n\n![{0} stats]({1})\n\n![{0} points]({2})\n",
+                row.name, row.stats_image, row.points_image
+            ));
+        }
+    }
+
+    let report_path = output_dir.join(format!(
+        "{}.{}",
+        prefix,
+        if config.html { "html" } else { "md" }
+    ));
+    File::create(&report_path)
+        .unwrap()
+        .write_all(report.as_bytes())
+        .unwrap();
+    println!("Wrote {}", report_path.display());
+}
+
+//----------------------------------------------------------------------
+
+/// Tests each hash for correlation between coordinates scrambled with
+/// related seeds, for each `SeedDerivation`, and prints a markdown table of
+/// the results.  See `related_seed_correlation()`.
+///
+/// Both the same Sobol dimension (dimension 0 for both coordinates) and
+/// different dimensions (dimensions 0 and 1) are tested.  Note that two Owen
+/// scrambles of the same dimension are strongly correlated even with ideal
+/// scrambling, so the "ref" hash is the baseline to compare against.
+pub fn print_correlation_table(
+    hashes: &[(String, Box<dyn OwenScrambler>)],
+    point_count: u32,
+    seeds: u32,
+) {
+    println!(
+        "| Hash | Seeds | Corr. (same dim) | Disc. (same dim) | Corr. (dims 0, 1) | Disc. (dims 0, 1) |"
+    );
+    println!("|---|---|---|---|---|---|");
+    for (name, scrambler) in hashes.iter() {
+        let scrambler = &**scrambler;
+        for &derivation in SeedDerivation::ALL.iter() {
+            let same = related_seed_correlation(scrambler, derivation, (0, 0), point_count, seeds);
+            let different =
+                related_seed_correlation(scrambler, derivation, (0, 1), point_count, seeds);
+            println!(
+                "| {} | {} | {:.4} | {:.5} | {:.4} | {:.5} |",
+                name,
+                derivation.name(),
+                same.correlation,
+                same.discrepancy,
+                different.correlation,
+                different.discrepancy,
+            );
+        }
+    }
+    println!();
+    println!(
+        "Independent random coordinates have an expected correlation of about {:.4}.",
+        (2.0 / (std::f64::consts::PI * point_count as f64)).sqrt()
+    );
+    println!("Same-dimension results should be compared against \"ref\", not zero.");
+}
@@ -0,0 +1,359 @@
+//! The `OwenScrambler` trait, and the scramblers that implement it.
+//!
+//! This lets the samplers, statistics, and images work with any scrambling
+//! approach: the ground-truth reference scramble, the named Laine-Karras
+//! style hashes, generated `HashOp` sequences, or ad-hoc closures.
+
+use crate::hash_gen::{
+    exec_hash_slice, exec_hash_slice_batch, exec_hash_slice_inverse, CompiledHash, HashOp,
+};
+use crate::sobol::owen_scramble_reference_u32;
+
+/// A seedable Owen scramble of 32-bit values.
+pub trait OwenScrambler: Sync {
+    /// The scrambling hash itself, operating on bit-reversed values.
+    ///
+    /// That is, each bit of the output may only depend on the same and
+    /// lower bits of the input (and the seed).  This is the form that
+    /// Laine-Karras style hashes take, and is what the statistics measure.
+    fn hash(&self, x: u32, seed: u32) -> u32;
+
+    /// Owen scrambles `x`, where `x` is a fixed-point value in [0, 1) as
+    /// produced by `sobol::sobol_u32()`.
+    #[inline]
+    fn scramble(&self, x: u32, seed: u32) -> u32 {
+        self.hash(x.reverse_bits(), seed).reverse_bits()
+    }
+
+    /// Whether the scrambler already mixes its seed well, in which case the
+    /// samplers in `sobol` pass seeds to it as-is instead of randomizing
+    /// them first.
+    fn mixes_seed(&self) -> bool {
+        false
+    }
+
+    /// Hashes every value of `xs` in place, all with the same seed.
+    ///
+    /// Scramblers can override this with a faster batched implementation.
+    fn hash_slice(&self, xs: &mut [u32], seed: u32) {
+        for x in xs.iter_mut() {
+            *x = self.hash(*x, seed);
+        }
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// The "ground truth" SipHash-based Owen scramble.
+///
+/// See `sobol::owen_scramble_reference_u32()`.
+#[derive(Debug, Copy, Clone)]
+pub struct Reference;
+
+impl OwenScrambler for Reference {
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        owen_scramble_reference_u32(x.reverse_bits(), seed).reverse_bits()
+    }
+
+    #[inline]
+    fn scramble(&self, x: u32, seed: u32) -> u32 {
+        owen_scramble_reference_u32(x, seed)
+    }
+
+    /// SipHash mixes the seed already, and this keeps `sobol::sample_owen()`
+    /// with `Reference` identical to `sobol::sample_owen_reference()`.
+    fn mixes_seed(&self) -> bool {
+        true
+    }
+}
+
+/// No scrambling at all.  Useful for looking at the plain Sobol sequence.
+#[derive(Debug, Copy, Clone)]
+pub struct Unscrambled;
+
+impl OwenScrambler for Unscrambled {
+    #[inline]
+    fn hash(&self, x: u32, _seed: u32) -> u32 {
+        x
+    }
+
+    #[inline]
+    fn scramble(&self, x: u32, _seed: u32) -> u32 {
+        x
+    }
+}
+
+/// Wraps a `(x, seed) -> hash` closure as a scrambler, for quickly trying
+/// out hand-written hashes.  The closure is the `hash()` of the scrambler.
+#[derive(Debug, Copy, Clone)]
+pub struct FnScrambler<F>(pub F);
+
+impl<F> OwenScrambler for FnScrambler<F>
+where
+    F: Fn(u32, u32) -> u32 + Sync,
+{
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        (self.0)(x, seed)
+    }
+}
+
+impl OwenScrambler for [HashOp] {
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        exec_hash_slice(self, x, seed)
+    }
+
+    fn hash_slice(&self, xs: &mut [u32], seed: u32) {
+        exec_hash_slice_batch(self, xs, seed);
+    }
+}
+
+impl OwenScrambler for CompiledHash {
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        // Resolving the seed isn't worth it for a single value.
+        exec_hash_slice(self.ops(), x, seed)
+    }
+
+    fn hash_slice(&self, xs: &mut [u32], seed: u32) {
+        self.seeded(seed).hash_slice(xs);
+    }
+}
+
+/// The inverse of a `HashOp` sequence's hash, so that its scrambles can be
+/// undone: `Inverse(ops).scramble(ops.scramble(x, seed), seed) == x`.
+///
+/// See `hash_gen::exec_hash_slice_inverse()`.  Panics for seeds that the
+/// hash isn't invertible with, rather than returning a wrong unscramble.
+#[derive(Debug, Copy, Clone)]
+pub struct Inverse<'a>(pub &'a [HashOp]);
+
+impl<'a> OwenScrambler for Inverse<'a> {
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        exec_hash_slice_inverse(self.0, x, seed).unwrap_or_else(|| {
+            panic!("{:?} isn't invertible with seed {:#x}", self.0, seed);
+        })
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// Checks whether two scramblers compute the same `hash()` function, and
+/// returns the first `(x, seed)` counterexample found if they don't.
+///
+/// This first checks exhaustively on reduced widths: every `x` below
+/// `2^width` against every seed below `2^width` and every seed with only
+/// its top `width` bits set (to catch hashes that use the high bits of the
+/// seed).  It then checks `random_tests` fully random 32-bit `(x, seed)`
+/// pairs.
+///
+/// Hand-written hashes can be checked by wrapping them in `FnScrambler`.
+pub fn find_counterexample<A, B>(a: &A, b: &B, width: u32, random_tests: u32) -> Option<(u32, u32)>
+where
+    A: OwenScrambler + ?Sized,
+    B: OwenScrambler + ?Sized,
+{
+    use rayon::prelude::*;
+
+    let width = width.clamp(1, 16);
+    let differs = |x: u32, seed: u32| a.hash(x, seed) != b.hash(x, seed);
+
+    // Exhaustive.
+    let seeds = (0..(1u32 << width))
+        .flat_map(|s| std::iter::once(s).chain(Some(s << (32 - width)).filter(|_| s != 0)));
+    let counterexample = seeds
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .find_map_first(|seed| {
+            (0..(1u32 << width))
+                .find(|&x| differs(x, seed))
+                .map(|x| (x, seed))
+        });
+    if counterexample.is_some() {
+        return counterexample;
+    }
+
+    // Random.
+    (0..random_tests).into_par_iter().find_map_first(|_| {
+        let x = rand::random::<u32>();
+        let seed = rand::random::<u32>();
+        Some((x, seed)).filter(|_| differs(x, seed))
+    })
+}
+
+//----------------------------------------------------------------------
+
+/// The Laine-Karras style hashes discussed in the blog posts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LkHash {
+    /// The original Laine-Karras hash.
+    Original,
+
+    /// "Improved" version 2.  Not actually that good.
+    /// From https://psychopath.io/post/2021_01_02_sobol_sampling_take_2
+    V2,
+
+    /// Fast, reasonable quality.
+    /// From https://psychopath.io/post/2021_01_30_building_a_better_lk_hash
+    Fast,
+
+    /// Medium-fast, good quality.  This is what `owen_scramble_fast_u32()`
+    /// uses.
+    /// From https://psychopath.io/post/2021_01_30_building_a_better_lk_hash
+    Good,
+
+    /// From the updated version of the "better LK hash" post, fixing the
+    /// issue that Matt Pharr found.
+    Fixed,
+}
+
+impl LkHash {
+    pub const ALL: &'static [LkHash] = &[
+        LkHash::Original,
+        LkHash::V2,
+        LkHash::Fast,
+        LkHash::Good,
+        LkHash::Fixed,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LkHash::Original => "lk",
+            LkHash::V2 => "v2",
+            LkHash::Fast => "fast",
+            LkHash::Good => "good",
+            LkHash::Fixed => "fixed",
+        }
+    }
+
+    /// The inverse of `name()`.
+    pub fn from_name(name: &str) -> Option<LkHash> {
+        LkHash::ALL.iter().copied().find(|h| h.name() == name)
+    }
+
+    /// The same hash, expressed as a sequence of `HashOp`s.
+    pub fn ops(&self) -> &'static [HashOp] {
+        match *self {
+            LkHash::Original => &[
+                HashOp::Add(0),
+                HashOp::MulXor(0x6c50b47c),
+                HashOp::MulXor(0xb82f1e52),
+                HashOp::MulXor(0xc7afe638),
+                HashOp::MulXor(0x8d22f6e6),
+            ],
+            LkHash::V2 => &[
+                HashOp::Add(0),
+                HashOp::Xor(0xdc967795),
+                HashOp::Mul(0x97b754b7),
+                HashOp::Xor(0x866350b1),
+                HashOp::Mul(0x9e3779cd),
+            ],
+            LkHash::Fast => &[
+                HashOp::ShlAdd(2),
+                HashOp::MulXor(0xfe9b5742),
+                HashOp::Add(0),
+                HashOp::Mul(0),
+            ],
+            LkHash::Good => &[
+                HashOp::Mul(0x788aeeed),
+                HashOp::MulXor(0x41506a02),
+                HashOp::Add(0),
+                HashOp::Mul(0),
+                HashOp::MulXor(0x7483dc64),
+            ],
+            LkHash::Fixed => &[
+                HashOp::MulXor(0x3d20adea),
+                HashOp::SeedMix,
+                HashOp::MulXor(0x05526c56),
+                HashOp::MulXor(0x53a22864),
+            ],
+        }
+    }
+}
+
+impl OwenScrambler for LkHash {
+    #[inline]
+    fn hash(&self, x: u32, seed: u32) -> u32 {
+        let mut x = x;
+        match *self {
+            LkHash::Original => {
+                x = x.wrapping_add(seed);
+                x ^= x.wrapping_mul(0x6c50b47c);
+                x ^= x.wrapping_mul(0xb82f1e52);
+                x ^= x.wrapping_mul(0xc7afe638);
+                x ^= x.wrapping_mul(0x8d22f6e6);
+            }
+
+            LkHash::V2 => {
+                x = x.wrapping_add(seed);
+                x ^= 0xdc967795;
+                x = x.wrapping_mul(0x97b754b7);
+                x ^= 0x866350b1;
+                x = x.wrapping_mul(0x9e3779cd);
+            }
+
+            LkHash::Fast => {
+                x = x.wrapping_add(x << 2);
+                x ^= x.wrapping_mul(0xfe9b5742);
+                x = x.wrapping_add(seed);
+                x = x.wrapping_mul(seed | 1);
+            }
+
+            LkHash::Good => {
+                x = x.wrapping_mul(0x788aeeed);
+                x ^= x.wrapping_mul(0x41506a02);
+                x = x.wrapping_add(seed);
+                x = x.wrapping_mul(seed | 1);
+                x ^= x.wrapping_mul(0x7483dc64);
+            }
+
+            LkHash::Fixed => {
+                x ^= x.wrapping_mul(0x3d20adea);
+                x = x.wrapping_add(seed);
+                x = x.wrapping_mul((seed >> 16) | 1);
+                x ^= x.wrapping_mul(0x05526c56);
+                x ^= x.wrapping_mul(0x53a22864);
+            }
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counterexample_equal_hashes() {
+        for hash in LkHash::ALL.iter() {
+            assert_eq!(find_counterexample(hash, hash.ops(), 6, 10000), None);
+        }
+        let inverse = FnScrambler(|x: u32, seed: u32| {
+            let ops = LkHash::Fast.ops();
+            Inverse(ops).hash(ops.hash(x, seed), seed)
+        });
+        assert_eq!(find_counterexample(&inverse, &Unscrambled, 6, 10000), None);
+    }
+
+    #[test]
+    fn counterexample_different_hashes() {
+        let check = |a: &dyn OwenScrambler, b: &dyn OwenScrambler| {
+            let (x, seed) = find_counterexample(a, b, 4, 0).expect("no counterexample");
+            assert_ne!(a.hash(x, seed), b.hash(x, seed));
+        };
+        check(&LkHash::Good, &LkHash::Fixed);
+        check(
+            &CompiledHash::new(&[HashOp::Xor(1)]),
+            &CompiledHash::new(&[HashOp::Xor(2)]),
+        );
+
+        // Only differs for seeds with high bits set.
+        check(
+            &LkHash::Fixed,
+            &FnScrambler(|x, seed: u32| LkHash::Fixed.hash(x, seed & 0xffff)),
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Searching for new scrambling hashes, either randomly from a
+//! `HashTemplate` or by exhaustively enumerating small hash structures, and
+//! scoring them from their `stats`.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::bench::ns_per_hash_batched;
+use crate::hash_gen::{
+    enumerate_structures, hash_slice_cost, simplify_hash, CompiledHash, CostModel, HashOp,
+    HashTemplate, Operand, TemplateSlot,
+};
+use crate::stats::{
+    measure_stats, print_stats, score_hash, write_stats_image, write_stats_svg, ScoreConfig,
+    SeedSource, Stats,
+};
+
+/// Settings for `search_hashes()`.
+pub struct SearchConfig {
+    pub rounds: usize,           // How many hashes to generate and test.
+    pub candidate_count: usize,  // How many of the top hashes to keep.
+    pub stat_rounds: u32,        // Rounds of `measure_stats()` per hash.
+    pub pareto: bool,            // Keep the quality/cost Pareto front instead of the top hashes.
+    pub cost_model: CostModel,   // For estimating the cost of hashes.
+    pub score: ScoreConfig,      // For scoring hashes.
+    pub seed_source: SeedSource, // Seeds to measure statistics with.
+    pub template: HashTemplate,  // For generating new hashes.
+
+    // Successive halving settings.  If `min_stat_rounds` is set, hashes are
+    // first scored in brackets of `bracket_size` starting at that many
+    // rounds, and only the best `1 / eta` of each rung are promoted to the
+    // next rung at `eta` times as many rounds, up to `stat_rounds`.
+    pub min_stat_rounds: Option<u32>,
+    pub bracket_size: usize,
+    pub eta: usize,
+}
+
+/// Randomly searches for better hashes, and prints the result to console.
+/// Optionally also saves statistics png images of the top produced hashes,
+/// named `{image_prefix}_{nn}.png`, and svg images in addition to that.
+///
+/// All this does is generate hashes randomly from a template, and keep the
+/// highest-scoring ones.  No fancy mutation approaches or whatnot,
+/// unfortunately.
+///
+/// In Pareto mode, it instead keeps every hash that isn't beaten in both
+/// score and estimated cost by another hash, and prints them from cheapest
+/// to most expensive.
+///
+/// With successive halving enabled (see `SearchConfig`), far more hashes
+/// can be explored in the same time, since most are discarded after cheap
+/// low-round scoring.  Only hashes scored with the full `stat_rounds` are
+/// ever kept as candidates.  Note that the early rungs only look at the
+/// score, even in Pareto mode.
+pub fn search_hashes(config: &SearchConfig, image_prefix: Option<&Path>, with_svg: bool) {
+    struct Candidate {
+        hash: Vec<HashOp>,
+        score: f64,
+        cost: f64,
+        stats: Stats,
+    }
+
+    let rounds = config.rounds;
+
+    // Method to use to generate new hashes.  They're simplified right away,
+    // so the reported hashes and their costs are as cheap as possible.
+    let generate = || simplify_hash(&config.template.generate());
+
+    // Measures and scores a hash.  The stats are measured with the hash
+    // compiled, which is much faster than interpreting its ops.
+    let evaluate = |hash: Vec<HashOp>, stat_rounds: u32| {
+        let compiled = CompiledHash::new(&hash);
+        let stats = measure_stats(
+            &compiled,
+            stat_rounds,
+            config.seed_source,
+            config.score.needs_extended_stats(),
+            false,
+        );
+        Candidate {
+            score: score_hash(&hash[..], &stats, &config.score),
+            cost: hash_slice_cost(&hash, &config.cost_model),
+            hash,
+            stats,
+        }
+    };
+
+    // Adds a fully evaluated hash to the candidates, if it's good enough.
+    let consider = |candidates: &mut Vec<Candidate>, new: Candidate| {
+        if candidates.iter().any(|c| c.hash == new.hash) {
+            // Already have this exact hash.
+        } else if config.pareto {
+            // Add it to the front if nothing on the front dominates it,
+            // removing anything that it dominates.
+            let dominates = |a: &Candidate, b: &Candidate| {
+                a.score <= b.score && a.cost <= b.cost && (a.score < b.score || a.cost < b.cost)
+            };
+            if !candidates
+                .iter()
+                .any(|c| dominates(c, &new) || (c.score == new.score && c.cost == new.cost))
+            {
+                candidates.retain(|c| !dominates(&new, c));
+                candidates.push(new);
+                candidates.sort_unstable_by(|x, y| x.cost.partial_cmp(&y.cost).unwrap());
+            }
+        } else if candidates.len() < config.candidate_count.max(1)
+            || new.score < candidates.last().unwrap().score
+        {
+            // If it beats the current lowest-scoring hash, replace it.
+            if candidates.len() >= config.candidate_count.max(1) {
+                candidates.pop();
+            }
+            candidates.push(new);
+            candidates.sort_unstable_by(|x, y| x.score.partial_cmp(&y.score).unwrap());
+        }
+    };
+
+    //----------------
+    // Do actual optimization process.
+    //----------------
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    println!();
+    if let Some(min_stat_rounds) = config.min_stat_rounds {
+        let eta = config.eta.max(2);
+        let mut generated = 0;
+        while generated < rounds {
+            let bracket_size = config.bracket_size.max(1).min(rounds - generated);
+            let mut pool: Vec<Vec<HashOp>> = (0..bracket_size).map(|_| generate()).collect();
+            generated += bracket_size;
+
+            // Score at increasing fidelity, promoting the best each time.
+            let mut stat_rounds = min_stat_rounds.min(config.stat_rounds);
+            let mut rung = 0;
+            while stat_rounds < config.stat_rounds && pool.len() > 1 {
+                print!(
+                    "\rround {}/{}, rung {} ({} stat rounds)        ",
+                    generated, rounds, rung, stat_rounds
+                );
+                std::io::stdout().flush().unwrap();
+
+                let mut scored: Vec<Candidate> = pool
+                    .drain(..)
+                    .map(|hash| evaluate(hash, stat_rounds))
+                    .collect();
+                scored.sort_unstable_by(|x, y| x.score.partial_cmp(&y.score).unwrap());
+                let keep = scored.len().div_ceil(eta);
+                pool.extend(scored.into_iter().take(keep).map(|c| c.hash));
+
+                stat_rounds = stat_rounds.saturating_mul(eta as u32);
+                rung += 1;
+            }
+
+            // Full fidelity.
+            for hash in pool {
+                let new = evaluate(hash, config.stat_rounds);
+                consider(&mut candidates, new);
+            }
+        }
+    } else {
+        for round in 0..rounds {
+            print!("\rround {}/{}", round, rounds);
+            std::io::stdout().flush().unwrap();
+
+            // Generate and score a new hash.
+            let new = evaluate(generate(), config.stat_rounds);
+            consider(&mut candidates, new);
+        }
+    }
+    println!();
+
+    // Print out the top hashes, and (optionally) write statistics png images
+    // for them as well.
+    for (i, c) in candidates.iter().enumerate() {
+        println!("Score: {}", c.score);
+        println!("Cost: {}", c.cost);
+
+        print!("&[");
+        for p in c.hash.iter() {
+            print!("HashOp::{:?}, ", *p);
+        }
+        println!("]");
+        println!(
+            "Time: {:.2} ns/hash (interpreted, batched)",
+            ns_per_hash_batched(&c.hash[..], 1 << 22)
+        );
+        print_stats(c.stats);
+        println!();
+
+        if let Some(prefix) = image_prefix {
+            let path = format!("{}_{:02}", prefix.display(), i + 1);
+            write_stats_image(c.stats, &mut File::create(format!("{}.png", path)).unwrap());
+            if with_svg {
+                write_stats_svg(c.stats, &mut File::create(format!("{}.svg", path)).unwrap());
+            }
+        }
+    }
+}
+
+//----------------------------------------------------------------------
+
+/// Evaluates every hash structure up to `max_len` operations (see
+/// `enumerate_structures()`) with a few random draws of its constants, and
+/// ranks the structures by their best score.
+///
+/// The top structures are printed to the console, and the full ranked table
+/// is written as markdown to `table_path`.
+pub fn rank_structures(
+    max_len: usize,
+    shifts: &[u32],
+    draws: usize,
+    stat_rounds: u32,
+    top: usize,
+    score_config: &ScoreConfig,
+    table_path: &Path,
+) {
+    struct Row {
+        structure: HashTemplate,
+        best_score: f64,
+        mean_score: f64,
+        cost: f64,
+        best_hash: Vec<HashOp>,
+    }
+
+    let structures = enumerate_structures(max_len, shifts);
+    let cost_model = CostModel::default();
+
+    let mut rows = Vec::new();
+    println!();
+    for (i, structure) in structures.iter().enumerate() {
+        print!("\rstructure {}/{}", i, structures.len());
+        std::io::stdout().flush().unwrap();
+
+        // Structures without random constants only need one draw.
+        let has_random = structure.slots.iter().any(|slot| {
+            matches!(slot, TemplateSlot::Op { kinds, operand: Operand::Random }
+                if kinds.iter().any(|k| k.has_constant()))
+        });
+        let draws = if has_random { draws.max(1) } else { 1 };
+
+        let mut row = Row {
+            structure: structure.clone(),
+            best_score: f64::INFINITY,
+            mean_score: 0.0,
+            cost: 0.0,
+            best_hash: Vec::new(),
+        };
+        for _ in 0..draws {
+            let hash = structure.generate();
+            let compiled = CompiledHash::new(&hash);
+            let stats = measure_stats(
+                &compiled,
+                stat_rounds,
+                SeedSource::Random,
+                score_config.needs_extended_stats(),
+                false,
+            );
+            let score = score_hash(&hash[..], &stats, score_config);
+            row.mean_score += score / draws as f64;
+            if score < row.best_score {
+                row.cost = hash_slice_cost(&hash, &cost_model);
+                row.best_score = score;
+                row.best_hash = hash;
+            }
+        }
+        rows.push(row);
+    }
+    println!();
+
+    rows.sort_by(|a, b| a.best_score.partial_cmp(&b.best_score).unwrap());
+
+    // Print and write the results.
+    let mut table = String::new();
+    table.push_str("| Rank | Structure | Best score | Mean score | Cost | Best hash |\n");
+    table.push_str("|---:|---|---:|---:|---:|---|\n");
+    for (i, row) in rows.iter().enumerate() {
+        let hash: Vec<String> = row
+            .best_hash
+            .iter()
+            .map(|op| format!("HashOp::{:?}", op))
+            .collect();
+        if i < top {
+            println!(
+                "{:>4}  {:<40} best {:<10.5} mean {:<10.5} cost {}",
+                i + 1,
+                row.structure.to_string(),
+                row.best_score,
+                row.mean_score,
+                row.cost
+            );
+        }
+        table.push_str(&format!(
+            "| {} | `{}` | {:.5} | {:.5} | {} | `&[{}]` |\n",
+            i + 1,
+            row.structure,
+            row.best_score,
+            row.mean_score,
+            row.cost,
+            hash.join(", ")
+        ));
+    }
+    File::create(table_path)
+        .unwrap()
+        .write_all(table.as_bytes())
+        .unwrap();
+    println!("Wrote {}", table_path.display());
+}
@@ -1,10 +1,10 @@
 //! An implementation of the Sobol low discrepancy sequence.
 
-use super::hash_u32;
+use crate::hash_u32;
+use crate::scramble::{Inverse, LkHash, OwenScrambler};
 
-use super::hash_gen::{exec_hash_slice, HashOp};
-
-// The following `include` provides `MAX_DIMENSION` and `VECTORS`.
+// The following `include` provides `MAX_DIMENSION`, `VECTORS`, and
+// `PIXEL_INVERSE_MATRICES`.
 // See the build.rs file for how this included file is generated.
 include!(concat!(env!("OUT_DIR"), "/vectors.inc"));
 
@@ -34,15 +34,176 @@ pub fn sample_owen_reference(index: u32, dimension: u32, seed: u32) -> f32 {
     ))
 }
 
+/// Same as `sample_owen_fast()` except the sample index is first shuffled
+/// with a (fast) Owen scramble seeded by `shuffle_seed`.
+///
+/// `shuffle_seed` should be the same for all dimensions of a given point
+/// set, so that the dimensions stay matched up.  Because the shuffle is
+/// itself an Owen scramble of the index, every power-of-two-sized prefix of
+/// the shuffled sequence is still a full aligned block of the original.
+#[inline]
+pub fn sample_owen_fast_shuffled(index: u32, dimension: u32, shuffle_seed: u32, seed: u32) -> f32 {
+    let index = owen_scramble_fast_u32(index, shuffle_seed);
+    sample_owen_fast(index, dimension, seed)
+}
+
+/// Same as `sample_owen_fast_shuffled()` except it uses the slower
+/// "ground-truth" implementation of Owen scrambling for both the index
+/// shuffle and the sample scramble.
+#[inline]
+pub fn sample_owen_reference_shuffled(
+    index: u32,
+    dimension: u32,
+    shuffle_seed: u32,
+    seed: u32,
+) -> f32 {
+    let index = owen_scramble_reference_u32(index, shuffle_seed);
+    sample_owen_reference(index, dimension, seed)
+}
+
+/// Same as `sample()` except applies Owen scrambling using the given
+/// scrambler.
+///
+/// The seed is randomized before being passed to the scrambler, since many
+/// of the fast hashes need well-mixed seeds, unless the scrambler mixes the
+/// seed itself (see `OwenScrambler::mixes_seed()`).  `sample_owen_fast()` is
+/// equivalent to this with `LkHash::Good`, and `sample_owen_reference()` to
+/// this with `Reference`.
+#[inline]
+pub fn sample_owen<S>(scrambler: &S, index: u32, dimension: u32, seed: u32) -> f32
+where
+    S: OwenScrambler + ?Sized,
+{
+    let seed = scrambler_seed(scrambler, seed);
+    u32_to_0_1_f32(scrambler.scramble(sobol_u32(index, dimension), seed))
+}
+
+/// Same as `sample_owen()` except the sample index is first shuffled with
+/// the scrambler, seeded by `shuffle_seed`.  See
+/// `sample_owen_fast_shuffled()` for details.
+#[inline]
+pub fn sample_owen_shuffled<S>(
+    scrambler: &S,
+    index: u32,
+    dimension: u32,
+    shuffle_seed: u32,
+    seed: u32,
+) -> f32
+where
+    S: OwenScrambler + ?Sized,
+{
+    let index = scrambler.scramble(index, scrambler_seed(scrambler, shuffle_seed));
+    sample_owen(scrambler, index, dimension, seed)
+}
+
+/// Undoes the index shuffle of `sample_owen_shuffled()`: returns the
+/// original Sobol index that `shuffled_index` came from.
+///
+/// `inverse` must be the inverse of the scrambler used for the shuffle, e.g.
+/// `scramble::Inverse(hash_ops)`.
+#[inline]
+pub fn unshuffle_index<S>(inverse: &S, shuffled_index: u32, shuffle_seed: u32) -> u32
+where
+    S: OwenScrambler + ?Sized,
+{
+    inverse.scramble(shuffled_index, scrambler_seed(inverse, shuffle_seed))
+}
+
+/// Returns the Sobol index of the `n`th point that falls in the given pixel
+/// of a `2^pixel_bits` x `2^pixel_bits` grid over dimensions 0 and 1.
+///
+/// Every block of `2^(2 * pixel_bits)` consecutive indices has exactly one
+/// point in each pixel, so this enumerates a pixel's points in sequence
+/// order.  `n` must be less than `2^(32 - 2 * pixel_bits)`, and
+/// `pixel_bits` must be in [1, 16].
+///
+/// Implements the enumeration approach from "Enumerating Quasi-Monte Carlo
+/// Point Sequences in Elementary Intervals" by Gruenschloss et al., using
+/// the inverse matrices generated in build.rs.
+pub fn pixel_sample_index(pixel_bits: u32, x: u32, y: u32, n: u32) -> u32 {
+    assert!((1..=16).contains(&pixel_bits));
+    let k = pixel_bits;
+    let high = (n as u64) << (2 * k);
+    assert!(high <= u32::MAX as u64, "sample number out of range");
+
+    // Dimension 0 is the van der Corput sequence, so the low `k` bits of
+    // the index are just the bit-reversed x coordinate.
+    let low = x.reverse_bits() >> (32 - k);
+
+    // Solve for the middle `k` bits so that dimension 1 lands on the y
+    // coordinate.
+    let known = low | high as u32;
+    let target = y ^ (sobol_u32(known, 1) >> (32 - k));
+    let matrix = &PIXEL_INVERSE_MATRICES[k as usize - 1];
+    let mut middle = 0;
+    for (row, mask) in matrix.iter().enumerate().take(k as usize) {
+        if (target >> (k as usize - 1 - row)) & 1 != 0 {
+            middle ^= mask;
+        }
+    }
+
+    known | (middle << k)
+}
+
+/// Same as `pixel_sample_index()`, except for points that are Owen
+/// scrambled as by `sample_owen()`, with `seed_x` and `seed_y` being the
+/// seeds for dimensions 0 and 1.
+///
+/// Pixels are in terms of the 32-bit fixed-point values, so points very
+/// close to a pixel edge may round across it when converted to `f32`.
+pub fn pixel_sample_index_owen<S>(
+    scrambler: &S,
+    pixel_bits: u32,
+    x: u32,
+    y: u32,
+    n: u32,
+    seed_x: u32,
+    seed_y: u32,
+) -> u32
+where
+    S: OwenScrambler + ?Sized,
+{
+    // Owen scrambling permutes the pixel rows and columns, so we just need
+    // to find which unscrambled pixel is moved to the requested one.
+    let x = unscramble_prefix(scrambler, x, pixel_bits, scrambler_seed(scrambler, seed_x));
+    let y = unscramble_prefix(scrambler, y, pixel_bits, scrambler_seed(scrambler, seed_y));
+    pixel_sample_index(pixel_bits, x, y, n)
+}
+
 //----------------------------------------------------------------------
 
+/// Finds the `bits`-bit prefix that the scrambler maps to `prefix`.
+///
+/// Owen scrambling maps prefixes to prefixes, and each output bit only
+/// depends on the same and higher input bits, flipping if that input bit
+/// does.  So this works for any scrambler, one bit at a time from the top.
+fn unscramble_prefix<S>(scrambler: &S, prefix: u32, bits: u32, seed: u32) -> u32
+where
+    S: OwenScrambler + ?Sized,
+{
+    let target = prefix << (32 - bits);
+    let mut x = 0u32;
+    for b in 0..bits {
+        let bit = 1 << (31 - b);
+        if (scrambler.scramble(x, seed) ^ target) & bit != 0 {
+            x |= bit;
+        }
+    }
+    x >> (32 - bits)
+}
+
 /// Utility for converting a u32 to a float in [0.0, 1.0).
-fn u32_to_0_1_f32(n: u32) -> f32 {
+#[inline]
+pub fn u32_to_0_1_f32(n: u32) -> f32 {
     n as f32 * (1.0 / (1u64 << 32) as f32)
 }
 
 /// The actual core Sobol samplng code.  Used by the above functions.
-fn sobol_u32(index: u32, dimension: u32) -> u32 {
+///
+/// Returns the component as a 32-bit fixed-point value in [0, 1), which is
+/// the form Owen scrambling operates on.
+#[inline]
+pub fn sobol_u32(index: u32, dimension: u32) -> u32 {
     assert!(dimension < MAX_DIMENSION);
     let vecs = &VECTORS[dimension as usize];
 
@@ -61,14 +222,18 @@ fn sobol_u32(index: u32, dimension: u32) -> u32 {
 }
 
 /// Scrambles `n` using fast hash-based Owen scrambling.
+pub fn owen_scramble_fast_u32(x: u32, seed: u32) -> u32 {
+    scramble_fast(x, randomize_seed(seed))
+}
+
+/// The hash-based Owen scramble of `owen_scramble_fast_u32()`, for an
+/// already randomized seed.
 ///
 /// Various hashes are included below, and can be uncommented to try them out.
-pub fn owen_scramble_fast_u32(x: u32, seed: u32) -> u32 {
+#[inline(always)]
+fn scramble_fast(x: u32, seed: u32) -> u32 {
     let mut x = x.reverse_bits();
 
-    // Randomize the seed value.
-    let seed = hash_u32(seed, 0xa14a177d);
-
     // // Original Laine-Karras hash.
     // x = x.wrapping_add(seed);
     // x ^= x.wrapping_mul(0x6c50b47c);
@@ -111,6 +276,33 @@ pub fn owen_scramble_fast_u32(x: u32, seed: u32) -> u32 {
     x.reverse_bits()
 }
 
+/// The inverse of `owen_scramble_fast_u32()`.  Useful for undoing the
+/// index shuffle of `sample_owen_fast_shuffled()`.
+pub fn owen_unscramble_fast_u32(x: u32, seed: u32) -> u32 {
+    Inverse(LkHash::Good.ops()).scramble(x, randomize_seed(seed))
+}
+
+/// Randomizes a seed value before it's passed to a scrambling hash.
+#[inline]
+fn randomize_seed(seed: u32) -> u32 {
+    hash_u32(seed, 0xa14a177d)
+}
+
+/// The seed to pass to a scrambler for the given sampling seed: randomized
+/// unless the scrambler mixes its seed itself.  See
+/// `OwenScrambler::mixes_seed()`.
+#[inline]
+fn scrambler_seed<S>(scrambler: &S, seed: u32) -> u32
+where
+    S: OwenScrambler + ?Sized,
+{
+    if scrambler.mixes_seed() {
+        seed
+    } else {
+        randomize_seed(seed)
+    }
+}
+
 /// Same as `owen_scramble_fast_u32()` above, except uses a slower
 /// "ground truth" algorithm for Owen scrambling.
 pub fn owen_scramble_reference_u32(n: u32, seed: u32) -> u32 {
@@ -134,3 +326,237 @@ pub fn owen_scramble_reference_u32(n: u32, seed: u32) -> u32 {
 
     out_bits
 }
+
+//----------------------------------------------------------------------
+// Batched versions of the above, for generating many samples at once.
+//
+// These work on slices of values in place, and are written so that the
+// compiler can vectorize them: no per-value branching, and the Sobol
+// sampling works through fixed-size chunks that fit in SIMD registers.
+// How much faster they are depends a lot on the target: e.g. without
+// SSE4.1 on x86-64 there is no vectorized 32-bit multiply, so the hashes
+// gain little unless built with `-C target-cpu=native` or similar.
+
+/// Number of values that `sobol_u32_batch()` works on at a time.
+const BATCH_CHUNK: usize = 16;
+
+/// Same as `sobol_u32()`, but for every index in `xs`, replacing each index
+/// with its component.
+pub fn sobol_u32_batch(xs: &mut [u32], dimension: u32) {
+    assert!(dimension < MAX_DIMENSION);
+    let vecs = &VECTORS[dimension as usize];
+
+    for chunk in xs.chunks_mut(BATCH_CHUNK) {
+        // Only go as far as the highest index bit in the chunk.
+        let bits = 32 - chunk.iter().fold(0, |a, &i| a | i).leading_zeros() as usize;
+
+        let mut result = [0u32; BATCH_CHUNK];
+        for (bit, &v) in vecs.iter().enumerate().take(bits) {
+            for (r, &index) in result.iter_mut().zip(chunk.iter()) {
+                *r ^= v & ((index >> bit) & 1).wrapping_neg();
+            }
+        }
+        chunk.copy_from_slice(&result[..chunk.len()]);
+    }
+}
+
+/// Same as `owen_scramble_fast_u32()`, but scrambles every value in `xs`
+/// with the same seed.
+pub fn owen_scramble_fast_u32_batch(xs: &mut [u32], seed: u32) {
+    let seed = randomize_seed(seed);
+    for x in xs.iter_mut() {
+        *x = scramble_fast(*x, seed);
+    }
+}
+
+/// Same as `u32_to_0_1_f32()`, but converts every value in `xs` into the
+/// same position in `out`.
+pub fn u32_to_0_1_f32_batch(xs: &[u32], out: &mut [f32]) {
+    assert_eq!(xs.len(), out.len());
+    for (o, &x) in out.iter_mut().zip(xs.iter()) {
+        *o = u32_to_0_1_f32(x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scramble::Reference;
+
+    const LENGTHS: &[usize] = &[0, 1, 7, 8, 15, 16, 17, 100];
+
+    /// Test values, including the extremes.
+    fn test_values(len: usize, seed: u32) -> Vec<u32> {
+        (0..len as u32)
+            .map(|i| match i {
+                0 => 0,
+                1 => u32::MAX,
+                _ => hash_u32(i, seed),
+            })
+            .collect()
+    }
+
+    /// Checks that the `samples` first indices enumerated for each pixel
+    /// (or a spread of pixels, at high resolutions) are distinct, and that
+    /// `to_pixel` maps them back to that pixel.
+    fn check_pixel_indices<F, G>(pixel_bits: u32, samples: u32, index: F, to_pixel: G)
+    where
+        F: Fn(u32, u32, u32) -> u32,
+        G: Fn(u32) -> (u32, u32),
+    {
+        let res = 1u32 << pixel_bits;
+        let step = (res / 16).max(1);
+        for y in (0..res).step_by(step as usize) {
+            for x in (0..res).step_by(step as usize) {
+                let mut seen = std::collections::HashSet::new();
+                for n in 0..samples {
+                    let i = index(x, y, n);
+                    assert_eq!(to_pixel(i), (x, y), "bits {}, sample {}", pixel_bits, n);
+                    assert!(seen.insert(i), "bits {}, duplicate index {}", pixel_bits, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pixel_sample_index_in_pixel() {
+        for pixel_bits in [1, 2, 3, 4, 8, 15, 16].iter().copied() {
+            let shift = 32 - pixel_bits;
+            // Up to 32 samples, within the `2^(32 - 2 * pixel_bits)` there are.
+            let samples = 1 << (32 - 2 * pixel_bits).min(5);
+            check_pixel_indices(
+                pixel_bits,
+                samples,
+                |x, y, n| pixel_sample_index(pixel_bits, x, y, n),
+                |i| (sobol_u32(i, 0) >> shift, sobol_u32(i, 1) >> shift),
+            );
+        }
+    }
+
+    #[test]
+    fn pixel_sample_index_owen_in_pixel() {
+        let (seed_x, seed_y) = (0x12345678, 0x9abcdef0);
+        for pixel_bits in 1..=4 {
+            let shift = 32 - pixel_bits;
+            let owen_pixel = |scrambler: &dyn OwenScrambler, i: u32| {
+                let x = scrambler.scramble(sobol_u32(i, 0), scrambler_seed(scrambler, seed_x));
+                let y = scrambler.scramble(sobol_u32(i, 1), scrambler_seed(scrambler, seed_y));
+                (x >> shift, y >> shift)
+            };
+            let scramblers: [&dyn OwenScrambler; 2] = [&LkHash::Good, &Reference];
+            for &scrambler in scramblers.iter() {
+                check_pixel_indices(
+                    pixel_bits,
+                    8,
+                    |x, y, n| {
+                        pixel_sample_index_owen(scrambler, pixel_bits, x, y, n, seed_x, seed_y)
+                    },
+                    |i| owen_pixel(scrambler, i),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn owen_unscramble_fast_u32_round_trip() {
+        for seed in [0, 1, 31, 32, u32::MAX].iter().copied().chain(0..64) {
+            for x in test_values(256, seed) {
+                let scrambled = owen_scramble_fast_u32(x, seed);
+                assert_eq!(
+                    owen_unscramble_fast_u32(scrambled, seed),
+                    x,
+                    "seed {}",
+                    seed
+                );
+                let unscrambled = owen_unscramble_fast_u32(x, seed);
+                assert_eq!(
+                    owen_scramble_fast_u32(unscrambled, seed),
+                    x,
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unshuffle_index_round_trip() {
+        let seed = 0x2468ace0;
+        for hash in LkHash::ALL.iter() {
+            let inverse = Inverse(hash.ops());
+            for shuffle_seed in [0, 1, 31, 32, u32::MAX].iter().copied().chain(0..16) {
+                for index in test_values(64, shuffle_seed) {
+                    // The same index mapping that `sample_owen_shuffled()` uses.
+                    let shuffled = hash.scramble(index, scrambler_seed(hash, shuffle_seed));
+                    assert_eq!(
+                        sample_owen_shuffled(hash, index, 1, shuffle_seed, seed),
+                        sample_owen(hash, shuffled, 1, seed)
+                    );
+                    assert_eq!(
+                        unshuffle_index(&inverse, shuffled, shuffle_seed),
+                        index,
+                        "{:?} with shuffle seed {}",
+                        hash,
+                        shuffle_seed
+                    );
+                }
+            }
+        }
+
+        // `sample_owen_fast_shuffled()` shuffles with the fast scramble.
+        for shuffle_seed in 0..16 {
+            for index in test_values(64, shuffle_seed) {
+                let shuffled = owen_scramble_fast_u32(index, shuffle_seed);
+                assert_eq!(
+                    sample_owen_fast_shuffled(index, 1, shuffle_seed, seed),
+                    sample_owen_fast(shuffled, 1, seed)
+                );
+                assert_eq!(owen_unscramble_fast_u32(shuffled, shuffle_seed), index);
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_u32_batch_matches_scalar() {
+        for &len in LENGTHS.iter() {
+            for dimension in 0..MAX_DIMENSION {
+                // Small consecutive indices as well as arbitrary ones.
+                let small: Vec<u32> = (0..len as u32).collect();
+                for indices in [small, test_values(len, dimension)].iter() {
+                    let expected: Vec<u32> =
+                        indices.iter().map(|&i| sobol_u32(i, dimension)).collect();
+                    let mut batch = indices.clone();
+                    sobol_u32_batch(&mut batch, dimension);
+                    assert_eq!(batch, expected, "dimension {}", dimension);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn owen_scramble_fast_u32_batch_matches_scalar() {
+        for &len in LENGTHS.iter() {
+            for seed in 0..64 {
+                let xs = test_values(len, seed);
+                let expected: Vec<u32> = xs
+                    .iter()
+                    .map(|&x| owen_scramble_fast_u32(x, seed))
+                    .collect();
+                let mut batch = xs.clone();
+                owen_scramble_fast_u32_batch(&mut batch, seed);
+                assert_eq!(batch, expected, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn u32_to_0_1_f32_batch_matches_scalar() {
+        for &len in LENGTHS.iter() {
+            let xs = test_values(len, 1);
+            let expected: Vec<f32> = xs.iter().map(|&x| u32_to_0_1_f32(x)).collect();
+            let mut batch = vec![0.0; len];
+            u32_to_0_1_f32_batch(&xs, &mut batch);
+            assert_eq!(batch, expected);
+        }
+    }
+}
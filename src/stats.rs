@@ -3,68 +3,297 @@ use std::io::Write;
 
 use rayon::prelude::*;
 
+use crate::plot::{
+    colormap_diverging, colormap_viridis, text_width, Canvas, RasterCanvas, SvgCanvas, TEXT_HEIGHT,
+};
+use crate::pointset::{integration_variance, mean_discrepancy};
+use crate::scramble::OwenScrambler;
+use crate::{hash_combine, hash_u32};
+
 #[derive(Debug, Copy, Clone)]
 pub struct Stats {
     pub avalanche: [[f64; 32]; 32],
     pub avalanche_avg_bias: [[f64; 32]; 32], // Average avalanche bias over many seeds.
     pub tree_bias: [[f64; 32]; 32],
+    pub seed_avalanche: [[f64; 32]; 32], // [seed bit][output bit], like `avalanche`.
+    pub seed_tree_distance: [f64; 32],   // Per tree level, between seeds `s` and `s + 1`.
+
+    // Higher-order statistics.
+    pub bit_independence: [[f64; 32]; 32], // [out bit a][out bit b], a < b.  See below.
+    pub two_bit_avalanche: [[f64; 32]; 32], // [in bit i][in bit j], i < j.  See below.
+    pub output_entropy: [f64; 32],         // Per output bit, over seeds.  See below.
 }
 
+// The higher-order statistics in `Stats` are:
+//
+// - `bit_independence`: the bit independence criterion.  The correlation
+//   between output bits `a` and `b` changing when an input bit below `a` is
+//   flipped.  Ideally zero.
+// - `two_bit_avalanche`: the fraction of the output bits above `j` that
+//   change when input bits `i` and `j` are flipped together.  Ideally 0.5.
+// - `output_entropy`: the entropy, in bits, of each output bit over seeds,
+//   for a fixed input.  Ideally 1.0.
+
 pub const STATS_ZERO: Stats = Stats {
     avalanche: [[0.0; 32]; 32],
     avalanche_avg_bias: [[0.0; 32]; 32],
     tree_bias: [[0.0; 32]; 32],
+    seed_avalanche: [[0.0; 32]; 32],
+    seed_tree_distance: [0.0; 32],
+    bit_independence: [[0.0; 32]; 32],
+    two_bit_avalanche: [[0.0; 32]; 32],
+    output_entropy: [0.0; 32],
 };
 
-/// Measures the statistics of the provided hash function.
-pub fn measure_stats<F>(hash: F, rounds: u32, print_progress: bool) -> Stats
+/// The expected average avalanche bias of a proper full Owen scramble, per
+/// output bit.
+///
+/// The first sixteen values here were computed analytically, and the
+/// remaining were approximated following a strong trend in the values by
+/// that point, and should be "reasonably" accurate.
+pub const TARGET_BIAS: [f64; 32] = [
+    0.0, 1.0, 0.5, 0.375, 0.273437, 0.19638, 0.139949, 0.099346, 0.070386, 0.049819, 0.035244,
+    0.024927, 0.017628, 0.012466, 0.008815, 0.006233, 0.004407, 0.003117, 0.002204, 0.001558,
+    0.001102, 0.000779, 0.000551, 0.000390, 0.000275, 0.000195, 0.000138, 0.000097, 0.000069,
+    0.000049, 0.000034, 0.000024,
+];
+
+/// Where `measure_stats()` gets its seeds from.
+///
+/// Renderers rarely use uniformly random seeds, so the structured sources
+/// are useful for checking whether a hash degrades on realistic seed
+/// patterns.  The seeds are passed to the hash as-is, without the seed
+/// randomization that `sobol::sample_owen()` does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeedSource {
+    /// Uniformly random seeds.
+    Random,
+
+    /// Consecutive seeds from a random starting point, like per-pixel
+    /// seeds.
+    Sequential,
+
+    /// Seeds with at most two bits set.
+    LowPopcount,
+
+    /// `hash_combine()` of a random base seed with consecutive values, like
+    /// per-dimension seeds.
+    HashCombine,
+
+    /// Seeds from a random starting point that each differ from the
+    /// previous one in a single bit, following a Gray code.
+    GrayCode,
+}
+
+impl SeedSource {
+    pub const ALL: &'static [SeedSource] = &[
+        SeedSource::Random,
+        SeedSource::Sequential,
+        SeedSource::LowPopcount,
+        SeedSource::HashCombine,
+        SeedSource::GrayCode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SeedSource::Random => "random",
+            SeedSource::Sequential => "sequential",
+            SeedSource::LowPopcount => "low-popcount",
+            SeedSource::HashCombine => "hash-combine",
+            SeedSource::GrayCode => "gray-code",
+        }
+    }
+
+    /// The `n`th seed of the source, where `base` is a random value that
+    /// stays the same for the whole measurement.
+    pub fn seed(&self, n: u32, base: u32) -> u32 {
+        match *self {
+            SeedSource::Random => rand::random::<u32>(),
+            SeedSource::Sequential => base.wrapping_add(n),
+            SeedSource::LowPopcount => {
+                // Zero, then the 32 single-bit seeds, then the 496 two-bit
+                // seeds, repeating.
+                let n = n % (1 + 32 + 32 * 31 / 2);
+                if n == 0 {
+                    0
+                } else if n <= 32 {
+                    1 << (n - 1)
+                } else {
+                    let mut i = n - 33;
+                    let mut high = 1;
+                    while i >= high {
+                        i -= high;
+                        high += 1;
+                    }
+                    (1 << high) | (1 << i)
+                }
+            }
+            SeedSource::HashCombine => hash_combine(base, n),
+            SeedSource::GrayCode => base ^ n ^ (n >> 1),
+        }
+    }
+}
+
+impl std::str::FromStr for SeedSource {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<SeedSource, String> {
+        SeedSource::ALL
+            .iter()
+            .copied()
+            .find(|s| s.name() == text)
+            .ok_or_else(|| {
+                let names: Vec<_> = SeedSource::ALL.iter().map(|s| s.name()).collect();
+                format!("expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// Measures the statistics of the provided scrambler's hash function, with
+/// seeds from the given source.
+///
+/// Unless `extended` is true, only the avalanche, average bias, and tree
+/// bias are measured, and the other statistics are set to NaN.  That's
+/// several times faster, which matters for searches that don't score the
+/// others.  See `ScoreConfig::needs_extended_stats()`.
+pub fn measure_stats<S>(
+    scrambler: &S,
+    rounds: u32,
+    seeds: SeedSource,
+    extended: bool,
+    print_progress: bool,
+) -> Stats
 where
-    F: Fn(u32, u32) -> u32 + Sync, // (input, seed) -> output
+    S: OwenScrambler + ?Sized,
 {
+    let hash = |x, seed| scrambler.hash(x, seed);
+    let base_seed = rand::random::<u32>();
+
     // Break up the rounds into chunks that we can hoist off to different
     // threads.
     let sub_rounds = 4096;
+    // `is_multiple_of()` would need Rust 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
     let loop_rounds = (rounds / sub_rounds) + ((rounds % sub_rounds) != 0) as u32;
-    let rounds = loop_rounds * sub_rounds;
+    let rounds = loop_rounds as u64 * sub_rounds as u64;
 
     if print_progress {
         print!("Progress..");
-        std::io::stdout().flush();
+        std::io::stdout().flush().unwrap();
     }
-    let data = (0..loop_rounds)
+    let counts = (0..loop_rounds)
         .into_par_iter()
         .map(|lr| {
             if print_progress && (lr % (loop_rounds / 53).max(1)) == 0 {
                 let stdout = std::io::stdout();
                 let mut out = stdout.lock();
-                out.write_all(b".");
-                out.flush();
+                out.write_all(b".").unwrap();
+                out.flush().unwrap();
             }
 
-            // Run tests and collect data.
-            let seed = rand::random::<u32>();
-            let mut data = STATS_ZERO;
-            for i in 0..sub_rounds {
-                // Avalanche and avalanche bias.
-                let input_1 = rand::random::<u32>();
-                let output_1 = hash(input_1, seed);
-                for bit_in in 0..32 {
-                    let input_2 = input_1 ^ (1 << bit_in);
-                    let output_2 = hash(input_2, seed);
-                    let diff_1 = output_1 ^ output_2;
-                    for bit_out in 0..32 {
-                        if (diff_1 & (1 << bit_out)) != 0 {
-                            data.avalanche[bit_in][bit_out] += 1.0;
-                            data.avalanche_avg_bias[bit_in][bit_out] += 1.0;
+            // A fast source of random numbers for this chunk.
+            let key = rand::random::<u32>();
+            let mut counter = 0u32;
+            let mut random = || {
+                counter = counter.wrapping_add(1);
+                hash_u32(counter, key)
+            };
+
+            // Run tests and collect data.  Anything counted per output bit
+            // goes through a `BitCounter`, which avoids per-bit branching.
+            // Most of the tests use the chunk's seed (or seeds derived from
+            // it), so they hash all of the chunk's inputs at once with
+            // `hash_slice()`.
+            let seed = seeds.seed(lr, base_seed);
+            let n = sub_rounds as usize;
+            let inputs: Vec<u32> = (0..n).map(|_| random()).collect();
+            let mut outputs = inputs.clone();
+            scrambler.hash_slice(&mut outputs, seed);
+            let mut diffs = vec![0u32; n];
+            let mut avalanche = vec![BitCounter::new(); 32];
+            let mut seed_avalanche = vec![BitCounter::new(); 32];
+            let mut bic_changed = vec![BitCounter::new(); 32];
+            let mut bic_both = vec![BitCounter::new(); 32];
+            let mut seed_tree_distance = BitCounter::new();
+            let mut entropy = BitCounter::new();
+            let mut data = Counts::zero();
+
+            // Avalanche and avalanche bias.  Bit independence is also
+            // measured here, using the flip of one random input bit per
+            // round to keep it cheap.
+            let bic_bits: Vec<usize> = if extended {
+                (0..n).map(|_| random() as usize % 31).collect()
+            } else {
+                Vec::new()
+            };
+            for (bit_in, counter) in avalanche.iter_mut().enumerate() {
+                hash_diffs(
+                    scrambler,
+                    &inputs,
+                    &outputs,
+                    |_| 1 << bit_in,
+                    seed,
+                    &mut diffs,
+                );
+                diffs.iter().for_each(|&diff| counter.add(diff));
+                for (&diff, &bic_bit) in diffs.iter().zip(bic_bits.iter()) {
+                    if bic_bit == bit_in {
+                        data.bic_flips[bit_in] += 1;
+                        for a in (bit_in + 1)..32 {
+                            bic_changed[a].add(diff);
+                            if (diff & (1 << a)) != 0 {
+                                bic_both[a].add(diff);
+                            }
                         }
                     }
                 }
+            }
+
+            // The seed and higher-order tests.
+            if extended {
+                // Two-bit-flip avalanche, again for one random pair of input
+                // bits per round.
+                let pairs: Vec<(usize, usize)> = (0..n)
+                    .map(|_| {
+                        let x = random() % 31;
+                        let y = (x + 1 + random() % 30) % 31;
+                        (x.min(y) as usize, x.max(y) as usize)
+                    })
+                    .collect();
+                let flip_pair = |i: usize| (1 << pairs[i].0) ^ (1 << pairs[i].1);
+                hash_diffs(scrambler, &inputs, &outputs, flip_pair, seed, &mut diffs);
+                for (&diff, &(bit_i, bit_j)) in diffs.iter().zip(pairs.iter()) {
+                    data.two_bit_changed[bit_i][bit_j] += (diff >> (bit_j + 1)).count_ones() as u64;
+                    data.two_bit_n[bit_i][bit_j] += 1;
+                }
+
+                // Seed avalanche.
+                for (bit_seed, counter) in seed_avalanche.iter_mut().enumerate() {
+                    let seed = seed ^ (1 << bit_seed);
+                    hash_diffs(scrambler, &inputs, &outputs, |_| 0, seed, &mut diffs);
+                    diffs.iter().for_each(|&diff| counter.add(diff));
+                }
+
+                // Scramble tree distance between neighbouring seeds.  Output
+                // bit N differing means the scramble trees disagree on the flip
+                // at the level N node that the input passes through.
+                let seed_1 = seed.wrapping_add(1);
+                hash_diffs(scrambler, &inputs, &outputs, |_| 0, seed_1, &mut diffs);
+                diffs.iter().for_each(|&diff| seed_tree_distance.add(diff));
+            }
+
+            // The remaining tests need a new seed every round.
+            let entropy_input = random();
+            for i in 0..sub_rounds {
+                // The per-round seeds are numbered after the per-chunk ones,
+                // so that they don't repeat them.
+                let n = loop_rounds.wrapping_add(lr.wrapping_mul(sub_rounds).wrapping_add(i));
+                let seed2 = seeds.seed(n, base_seed);
 
                 // Tree seeding bias.
-                let seed2 = rand::random::<u32>();
-                let input_3 = rand::random::<u32>();
+                let input_3 = random();
                 let output_3 = hash(input_3, seed2);
-                let input_4 = rand::random::<u32>();
+                let input_4 = random();
                 let output_4 = hash(input_4, seed2);
                 let mut x = output_3 ^ output_4;
                 let mut y = input_3 ^ input_4;
@@ -74,32 +303,38 @@ where
                 }
                 y = y.reverse_bits() >> 26;
                 x = x.reverse_bits() >> 26;
-                data.tree_bias[x as usize & 0b11111][y as usize & 0b11111] += 0.5;
+                data.tree_bias[x as usize & 0b11111][y as usize & 0b11111] += 1;
+
+                // Output entropy over seeds.
+                if extended {
+                    entropy.add(hash(entropy_input, seed2));
+                }
             }
 
             // Process data.
-            for i in 0..32 {
-                for j in 0..32 {
-                    data.avalanche_avg_bias[i][j] =
-                        (data.avalanche_avg_bias[i][j] - (0.5 * sub_rounds as f64)).abs();
+            for bit_in in 0..32 {
+                data.avalanche[bit_in] = *avalanche[bit_in].counts();
+                for (bias, &count) in data.avalanche_bias[bit_in]
+                    .iter_mut()
+                    .zip(data.avalanche[bit_in].iter())
+                {
+                    *bias = (2 * count).abs_diff(sub_rounds as u64);
                 }
+                data.seed_avalanche[bit_in] = *seed_avalanche[bit_in].counts();
+                data.bic_changed[bit_in] = *bic_changed[bit_in].counts();
+                data.bic_both[bit_in] = *bic_both[bit_in].counts();
+            }
+            data.seed_tree_distance = *seed_tree_distance.counts();
+            for (e, &ones) in data.output_entropy.iter_mut().zip(entropy.counts().iter()) {
+                *e = binary_entropy(ones as f64 / sub_rounds as f64);
             }
 
             data
         })
-        .reduce(
-            || STATS_ZERO,
-            |mut a, b| {
-                for i in 0..32 {
-                    for j in 0..32 {
-                        a.avalanche[i][j] += b.avalanche[i][j];
-                        a.avalanche_avg_bias[i][j] += b.avalanche_avg_bias[i][j];
-                        a.tree_bias[i][j] += b.tree_bias[i][j];
-                    }
-                }
-                a
-            },
-        );
+        .reduce(Counts::zero, |mut a, b| {
+            a.add(&b);
+            a
+        });
     if print_progress {
         print!(
             "\r                                                                                \r"
@@ -107,72 +342,893 @@ where
     }
 
     let mut stats = STATS_ZERO;
+    let rounds = rounds as f64;
     for i in 0..32 {
         for j in 0..32 {
-            stats.avalanche[i][j] += data.avalanche[i][j] / rounds as f64;
-            stats.avalanche_avg_bias[i][j] += data.avalanche_avg_bias[i][j] * 2.0 / rounds as f64;
-            stats.tree_bias[i][j] += data.tree_bias[i][j] / rounds as f64 * 32.0 * 32.0;
+            stats.avalanche[i][j] = counts.avalanche[i][j] as f64 / rounds;
+            stats.avalanche_avg_bias[i][j] = counts.avalanche_bias[i][j] as f64 / rounds;
+            stats.tree_bias[i][j] = counts.tree_bias[i][j] as f64 * 0.5 / rounds * 32.0 * 32.0;
+            stats.seed_avalanche[i][j] = counts.seed_avalanche[i][j] as f64 / rounds;
+            if counts.two_bit_n[i][j] > 0 {
+                stats.two_bit_avalanche[i][j] = counts.two_bit_changed[i][j] as f64
+                    / (31 - j) as f64
+                    / counts.two_bit_n[i][j] as f64;
+            }
         }
+        stats.seed_tree_distance[i] = counts.seed_tree_distance[i] as f64 / rounds;
+        stats.output_entropy[i] = counts.output_entropy[i] / loop_rounds as f64;
+    }
+
+    // Bit independence: the Pearson correlation of the two bits changing,
+    // over the flips of input bits below `a`.
+    for a in 0..32 {
+        let n = counts.bic_flips[..a].iter().sum::<u64>() as f64;
+        let changed_a = counts.bic_changed[a][a] as f64;
+        for b in (a + 1)..32 {
+            let changed_b = counts.bic_changed[a][b] as f64;
+            let both = counts.bic_both[a][b] as f64;
+            let denom = ((n * changed_a - changed_a * changed_a)
+                * (n * changed_b - changed_b * changed_b))
+                .sqrt();
+            if denom > 0.0 {
+                stats.bit_independence[a][b] = (n * both - changed_a * changed_b) / denom;
+            }
+        }
+    }
+
+    if !extended {
+        stats.seed_avalanche = [[f64::NAN; 32]; 32];
+        stats.seed_tree_distance = [f64::NAN; 32];
+        stats.bit_independence = [[f64::NAN; 32]; 32];
+        stats.two_bit_avalanche = [[f64::NAN; 32]; 32];
+        stats.output_entropy = [f64::NAN; 32];
     }
 
     stats
 }
 
+/// Weights and settings for scoring hashes.  See `score_hash()`.
+///
+/// A weight of zero disables that metric entirely, which for the point set
+/// metrics also skips computing them.
+#[derive(Debug, Copy, Clone)]
+pub struct ScoreConfig {
+    pub avalanche: f64,         // Deviation of the avalanche from 0.5.
+    pub avg_bias: f64,          // Deviation of the average bias from `TARGET_BIAS`.
+    pub tree_bias: f64,         // Deviation of the tree bias from 0.5.
+    pub seed_avalanche: f64,    // Deviation of the seed avalanche from 0.5.
+    pub tree_distance: f64,     // Deviation of the neighbouring seed tree distance from 0.5.
+    pub bit_independence: f64,  // Output bit change correlations.
+    pub two_bit_avalanche: f64, // Deviation of the two-bit avalanche from 0.5.
+    pub entropy: f64,           // Deviation of the output bit entropies from 1.0.
+    pub discrepancy: f64,       // Average L2-star discrepancy of scrambled points.
+    pub variance: f64,          // Relative integration variance of scrambled points.
+
+    // Point set settings for the discrepancy and variance metrics.
+    pub points: u32,
+    pub seeds: u32,
+}
+
+impl Default for ScoreConfig {
+    /// The defaults are the scoring that the hash search has always used.
+    fn default() -> ScoreConfig {
+        ScoreConfig {
+            avalanche: 1.0,
+            avg_bias: 1.0,
+            tree_bias: 0.0,
+            seed_avalanche: 0.0,
+            tree_distance: 0.0,
+            bit_independence: 0.0,
+            two_bit_avalanche: 0.0,
+            entropy: 0.0,
+            discrepancy: 0.0,
+            variance: 0.0,
+            points: 256,
+            seeds: 16,
+        }
+    }
+}
+
+impl ScoreConfig {
+    /// Whether any of the weighted metrics need more than the statistics
+    /// that `measure_stats()` measures by default.
+    pub fn needs_extended_stats(&self) -> bool {
+        self.seed_avalanche != 0.0
+            || self.tree_distance != 0.0
+            || self.bit_independence != 0.0
+            || self.two_bit_avalanche != 0.0
+            || self.entropy != 0.0
+    }
+}
+
+impl std::str::FromStr for ScoreConfig {
+    type Err = String;
+
+    /// Parses a comma-separated list of `name=value` pairs, e.g.
+    /// "tree=0.5,discrepancy=10,points=1024".  Weight names are
+    /// `avalanche`, `bias`, `tree`, `seed`, `tree-distance`, `bic`,
+    /// `avalanche2`, `entropy`, `discrepancy`, and `variance`, and the point
+    /// set settings are `points` and `seeds`, which must be whole numbers.
+    /// Unlisted values are left at their defaults.
+    fn from_str(text: &str) -> Result<ScoreConfig, String> {
+        let mut config = ScoreConfig::default();
+        for item in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = item.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let value = parts.next().unwrap_or("").trim();
+            let invalid = || format!("invalid value in \"{}\"", item);
+
+            // The point set settings are counts, not weights.
+            if name == "points" || name == "seeds" {
+                let count = value.parse::<u32>().map_err(|_| invalid())?;
+                if name == "points" {
+                    config.points = count;
+                } else {
+                    config.seeds = count;
+                }
+                continue;
+            }
+
+            let value = value.parse::<f64>().map_err(|_| invalid())?;
+            match name {
+                "avalanche" => config.avalanche = value,
+                "bias" => config.avg_bias = value,
+                "tree" => config.tree_bias = value,
+                "seed" => config.seed_avalanche = value,
+                "tree-distance" => config.tree_distance = value,
+                "bic" => config.bit_independence = value,
+                "avalanche2" => config.two_bit_avalanche = value,
+                "entropy" => config.entropy = value,
+                "discrepancy" => config.discrepancy = value,
+                "variance" => config.variance = value,
+                _ => return Err(format!("unknown metric \"{}\"", name)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Hashes each `inputs[i] ^ flip(i)` with `seed`, and stores how it
+/// differs from `outputs[i]` in `diffs[i]`.
+fn hash_diffs<S, F>(
+    scrambler: &S,
+    inputs: &[u32],
+    outputs: &[u32],
+    flip: F,
+    seed: u32,
+    diffs: &mut [u32],
+) where
+    S: OwenScrambler + ?Sized,
+    F: Fn(usize) -> u32,
+{
+    for (i, (diff, &x)) in diffs.iter_mut().zip(inputs.iter()).enumerate() {
+        *diff = x ^ flip(i);
+    }
+    scrambler.hash_slice(diffs, seed);
+    for (diff, &y) in diffs.iter_mut().zip(outputs.iter()) {
+        *diff ^= y;
+    }
+}
+
+/// Integer counts accumulated by `measure_stats()`, before being turned
+/// into `Stats`.  The matrices are boxed to keep them off the (rayon
+/// thread) stacks.
+struct Counts {
+    avalanche: Box<[[u64; 32]; 32]>,
+    avalanche_bias: Box<[[u64; 32]; 32]>, // Sum over chunks of |2 * count - chunk size|.
+    tree_bias: Box<[[u64; 32]; 32]>,
+    seed_avalanche: Box<[[u64; 32]; 32]>,
+    seed_tree_distance: [u64; 32],
+    bic_flips: [u64; 32], // How many times each input bit was used for BIC.
+    bic_changed: Box<[[u64; 32]; 32]>, // [a][b]: b changed, for flips below a.
+    bic_both: Box<[[u64; 32]; 32]>, // [a][b]: a and b changed, for flips below a.
+    two_bit_changed: Box<[[u64; 32]; 32]>, // Changed output bits above j.
+    two_bit_n: Box<[[u64; 32]; 32]>,
+    output_entropy: [f64; 32], // Sum over chunks.
+}
+
+impl Counts {
+    fn zero() -> Counts {
+        Counts {
+            avalanche: Box::new([[0; 32]; 32]),
+            avalanche_bias: Box::new([[0; 32]; 32]),
+            tree_bias: Box::new([[0; 32]; 32]),
+            seed_avalanche: Box::new([[0; 32]; 32]),
+            seed_tree_distance: [0; 32],
+            bic_flips: [0; 32],
+            bic_changed: Box::new([[0; 32]; 32]),
+            bic_both: Box::new([[0; 32]; 32]),
+            two_bit_changed: Box::new([[0; 32]; 32]),
+            two_bit_n: Box::new([[0; 32]; 32]),
+            output_entropy: [0.0; 32],
+        }
+    }
+
+    fn add(&mut self, other: &Counts) {
+        for i in 0..32 {
+            for j in 0..32 {
+                self.avalanche[i][j] += other.avalanche[i][j];
+                self.avalanche_bias[i][j] += other.avalanche_bias[i][j];
+                self.tree_bias[i][j] += other.tree_bias[i][j];
+                self.seed_avalanche[i][j] += other.seed_avalanche[i][j];
+                self.bic_changed[i][j] += other.bic_changed[i][j];
+                self.bic_both[i][j] += other.bic_both[i][j];
+                self.two_bit_changed[i][j] += other.two_bit_changed[i][j];
+                self.two_bit_n[i][j] += other.two_bit_n[i][j];
+            }
+            self.seed_tree_distance[i] += other.seed_tree_distance[i];
+            self.bic_flips[i] += other.bic_flips[i];
+            self.output_entropy[i] += other.output_entropy[i];
+        }
+    }
+}
+
+/// Counts how many times each bit position is set over many `u32` words.
+///
+/// The counts are kept bit-sliced: bit `b` of `planes[p]` is bit `p` of the
+/// count for bit position `b`.  Adding a word is then a ripple-carry add
+/// across the planes, which counts all 32 bit positions at once.  The
+/// planes are flushed into the full counts before they can overflow.
+#[derive(Copy, Clone)]
+struct BitCounter {
+    planes: [u32; 8],
+    len: u32, // Words added since the last flush.
+    counts: [u64; 32],
+}
+
+impl BitCounter {
+    fn new() -> BitCounter {
+        BitCounter {
+            planes: [0; 8],
+            len: 0,
+            counts: [0; 32],
+        }
+    }
+
+    #[inline(always)]
+    fn add(&mut self, word: u32) {
+        let mut carry = word;
+        for plane in self.planes.iter_mut() {
+            let next_carry = *plane & carry;
+            *plane ^= carry;
+            carry = next_carry;
+        }
+        self.len += 1;
+        if self.len == (1 << self.planes.len()) - 1 {
+            self.flush();
+        }
+    }
+
+    /// The count for each bit position, of all words added so far.
+    fn counts(&mut self) -> &[u64; 32] {
+        self.flush();
+        &self.counts
+    }
+
+    fn flush(&mut self) {
+        for (bit, count) in self.counts.iter_mut().enumerate() {
+            let mut n = 0;
+            for (p, plane) in self.planes.iter().enumerate() {
+                n |= ((plane >> bit) & 1) << p;
+            }
+            *count += n as u64;
+        }
+        self.planes = [0; 8];
+        self.len = 0;
+    }
+}
+
+/// The entropy in bits of a binary random variable with probability `p`.
+fn binary_entropy(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        0.0
+    } else {
+        -(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+    }
+}
+
+/// Scores the given hash statistics with the default scoring.  Used for
+/// searching for better hashes.
+///
+/// Lower score is better (like golf!).
+pub fn score_stats(stats: &Stats) -> f64 {
+    score_stats_weighted(stats, &ScoreConfig::default())
+}
+
+/// Scores the given hash statistics with the given weights.  The point set
+/// metrics need the scrambler itself, and are ignored here.  See
+/// `score_hash()` for those.
+pub fn score_stats_weighted(stats: &Stats, config: &ScoreConfig) -> f64 {
+    let mut score = 0.0;
+
+    // Avalanche metric.
+    if config.avalanche != 0.0 {
+        let mut sum = 0.0;
+        for bit_out in 0..32 {
+            for bit_in in 0..bit_out {
+                let diff = stats.avalanche[bit_in][bit_out] - 0.5;
+                sum += diff * diff;
+            }
+        }
+        score += sum * config.avalanche;
+    }
+
+    // Avalanche bias metric, trying to match the expected bias of a
+    // proper full Owen scramble.
+    if config.avg_bias != 0.0 {
+        let mut sum = 0.0;
+        for (bit_out, target) in TARGET_BIAS.iter().enumerate() {
+            for bit_in in 0..bit_out {
+                let diff = stats.avalanche_avg_bias[bit_in][bit_out] - target;
+                sum += diff * diff;
+            }
+        }
+        score += sum * config.avg_bias;
+    }
+
+    // Tree bias metric.
+    // With the new seed mixing op, this is usually unnecessary to target
+    // since it's pretty much always perfect, so it's off by default.
+    if config.tree_bias != 0.0 {
+        let mut sum = 0.0;
+        for x in 0..32 {
+            for y in (x + 1)..32 {
+                let diff = (stats.tree_bias[x][y] - 0.5) * 2.0;
+                sum += diff * diff;
+            }
+        }
+        score += sum * config.tree_bias;
+    }
+
+    // Seed avalanche metrics.
+    if config.seed_avalanche != 0.0 {
+        let mut sum = 0.0;
+        for row in stats.seed_avalanche.iter() {
+            for v in row.iter() {
+                sum += (v - 0.5) * (v - 0.5);
+            }
+        }
+        score += sum * config.seed_avalanche;
+    }
+    if config.tree_distance != 0.0 {
+        let sum: f64 = stats
+            .seed_tree_distance
+            .iter()
+            .map(|v| (v - 0.5) * (v - 0.5))
+            .sum();
+        score += sum * config.tree_distance;
+    }
+
+    // Higher-order metrics.
+    if config.bit_independence != 0.0 {
+        let mut sum = 0.0;
+        for a in 0..32 {
+            for b in (a + 1)..32 {
+                sum += stats.bit_independence[a][b] * stats.bit_independence[a][b];
+            }
+        }
+        score += sum * config.bit_independence;
+    }
+    if config.two_bit_avalanche != 0.0 {
+        let mut sum = 0.0;
+        for i in 0..31 {
+            for j in (i + 1)..31 {
+                let diff = stats.two_bit_avalanche[i][j] - 0.5;
+                sum += diff * diff;
+            }
+        }
+        score += sum * config.two_bit_avalanche;
+    }
+    if config.entropy != 0.0 {
+        let sum: f64 = stats
+            .output_entropy
+            .iter()
+            .map(|e| (1.0 - e) * (1.0 - e))
+            .sum();
+        score += sum * config.entropy;
+    }
+
+    score
+}
+
+/// Scores a hash from its statistics plus, if enabled in the config, the
+/// quality of the 2d point sets it produces.
+///
+/// Lower score is better.
+pub fn score_hash<S>(scrambler: &S, stats: &Stats, config: &ScoreConfig) -> f64
+where
+    S: OwenScrambler + ?Sized,
+{
+    let mut score = score_stats_weighted(stats, config);
+    if config.discrepancy != 0.0 {
+        score += mean_discrepancy(scrambler, config.points, config.seeds) * config.discrepancy;
+    }
+    if config.variance != 0.0 {
+        score += integration_variance(scrambler, config.points, config.seeds) * config.variance;
+    }
+    score
+}
+
+/// Prints a summary of the stats.  Statistics that weren't measured (see
+/// `measure_stats()`) are left out.
 pub fn print_stats(stats: Stats) {
     // Calculate reduced stats
     let mut reduced_stats = [0.0f64; 32]; // (avg, max)
     for bit_in in 0..32 {
-        for bit_out in (bit_in + 1)..32 {
-            reduced_stats[bit_out] += stats.avalanche_avg_bias[bit_in][bit_out] / bit_out as f64;
+        for (bit_out, reduced) in reduced_stats.iter_mut().enumerate().skip(bit_in + 1) {
+            *reduced += stats.avalanche_avg_bias[bit_in][bit_out] / bit_out as f64;
         }
     }
 
-    // Calculate average bias.
+    // Print info.
+    println!("Per-output-bit average bias:\n{:0.2?}", reduced_stats);
+    println!("Total average bias:\n{:0.3}", average_bias(&stats));
+    if stats.output_entropy.iter().all(|e| e.is_nan()) {
+        return;
+    }
+    println!(
+        "Seed avalanche average deviation from 0.5:\n{:0.3}",
+        stats
+            .seed_avalanche
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|v| (v - 0.5).abs())
+            .sum::<f64>()
+            / (32 * 32) as f64
+    );
+    println!(
+        "Per-level tree distance between neighbouring seeds:\n{:0.2?}",
+        stats.seed_tree_distance
+    );
+
+    let mut bic_sum = 0.0;
+    let mut two_bit_sum = 0.0;
+    for i in 0..32 {
+        for j in (i + 1)..32 {
+            bic_sum += stats.bit_independence[i][j].abs();
+            if j < 31 {
+                two_bit_sum += (stats.two_bit_avalanche[i][j] - 0.5).abs();
+            }
+        }
+    }
+    println!(
+        "Bit independence average absolute correlation:\n{:0.4}",
+        bic_sum / (32 * 31 / 2) as f64
+    );
+    println!(
+        "Two-bit avalanche average deviation from 0.5:\n{:0.4}",
+        two_bit_sum / (31 * 30 / 2) as f64
+    );
+    println!(
+        "Per-output-bit entropy over seeds:\n{:0.3?}",
+        stats.output_entropy
+    );
+}
+
+/// The average avalanche bias over all (input bit, output bit) pairs that
+/// matter for Owen scrambling.
+pub fn average_bias(stats: &Stats) -> f64 {
     let mut avg_bias = 0.0;
     for bit_in in 0..32 {
         for bit_out in (bit_in + 1)..32 {
             avg_bias += stats.avalanche_avg_bias[bit_in][bit_out];
         }
     }
-    avg_bias /= (32 * 31 / 2) as f64;
-
-    // Print info.
-    println!("Per-output-bit average bias:\n{:0.2?}", reduced_stats);
-    println!("Total average bias:\n{:0.3}", avg_bias);
+    avg_bias / (32 * 31 / 2) as f64
 }
 
+/// Writes a png image of the given stats.  See `draw_stats()` for what is
+/// drawn.
 pub fn write_stats_image(stats: Stats, file: &mut File) {
-    const BIT_PIXEL_SIZE: usize = 8;
-    const WIDTH: usize = BIT_PIXEL_SIZE * 32 * 3;
-    const HEIGHT: usize = BIT_PIXEL_SIZE * 32;
-    let mut image = vec![0x00u8; 4 * WIDTH * HEIGHT];
-    let mut plot = |x: usize, y: usize, color: u8| {
-        let min_x = x * BIT_PIXEL_SIZE;
-        let min_y = y * BIT_PIXEL_SIZE;
-        let max_x = min_x + BIT_PIXEL_SIZE;
-        let max_y = min_y + BIT_PIXEL_SIZE;
-
-        for y in min_y..max_y {
-            for x in min_x..max_x {
-                image[(y * WIDTH + x) * 4] = color;
-                image[(y * WIDTH + x) * 4 + 1] = color;
-                image[(y * WIDTH + x) * 4 + 2] = color;
-                image[(y * WIDTH + x) * 4 + 3] = 0xFF;
-            }
-        }
-    };
+    let (width, height) = stats_image_size();
+    let mut canvas = RasterCanvas::new(width, height, [0xff; 3]);
+    draw_stats(&stats, &mut canvas);
+    canvas.write_png(file);
+}
+
+/// Same as `write_stats_image()`, but writes an svg image instead.
+pub fn write_stats_svg(stats: Stats, file: &mut File) {
+    let (width, height) = stats_image_size();
+    let mut canvas = SvgCanvas::new(width, height, [0xff; 3]);
+    draw_stats(&stats, &mut canvas);
+    canvas.write_svg(file);
+}
+
+//----------------------------------------------------------------------
+
+const CELL_SIZE: usize = 8;
+const MAP_SIZE: usize = CELL_SIZE * 32;
+const PANEL_LEFT: usize = 48; // Space for the y axis ticks and label.
+const PANEL_TOP: usize = 28; // Space for the title.
+const PANEL_WIDTH: usize = PANEL_LEFT + MAP_SIZE + 24;
+const PANEL_HEIGHT: usize = PANEL_TOP + MAP_SIZE + 80;
+const PANEL_COLUMNS: usize = 3;
+const PANEL_ROWS: usize = 2;
+const STRIP_SIZE: usize = CELL_SIZE * 2; // Height of a single-row strip.
+const STRIP_PANEL_HEIGHT: usize = PANEL_TOP + STRIP_SIZE + 80;
+const TEXT_COLOR: [u8; 3] = [0x20, 0x20, 0x20];
+const NA_COLOR: [u8; 3] = [0xb0, 0xb0, 0xb0];
+
+fn stats_image_size() -> (usize, usize) {
+    (
+        PANEL_WIDTH * PANEL_COLUMNS,
+        PANEL_HEIGHT * PANEL_ROWS + STRIP_PANEL_HEIGHT,
+    )
+}
 
+/// Draws the stats as six annotated heatmaps, in two rows of three, followed
+/// by a row of strips.  Unless noted, each heatmap has the output bit on the
+/// x axis and the input (or seed) bit on the y axis:
+///
+/// 1. Avalanche minus the ideal of 0.5, with a diverging colormap.
+/// 2. Average avalanche bias minus `TARGET_BIAS`, with a diverging colormap.
+///    Entries that don't apply to Owen scrambling (input bit >= output bit)
+///    are drawn in gray.
+/// 3. Tree bias, with a perceptual sequential colormap.
+/// 4. Seed avalanche minus the ideal of 0.5, with a diverging colormap.
+/// 5. Bit independence, with output bits on both axes, with a diverging
+///    colormap.
+/// 6. Two-bit avalanche minus the ideal of 0.5, with input bits on both
+///    axes, with a diverging colormap.
+///
+/// The strips have the output bit on the x axis:
+///
+/// 7. One minus the output entropy, with a perceptual sequential colormap.
+///
+/// Statistics that weren't measured (NaN) are drawn in gray.
+fn draw_stats<C: Canvas>(stats: &Stats, canvas: &mut C) {
+    let mut avalanche = [[None; 32]; 32];
+    let mut bias = [[None; 32]; 32];
+    let mut tree = [[None; 32]; 32];
+    let mut seed = [[None; 32]; 32];
+    let mut bic = [[None; 32]; 32];
+    let mut two_bit = [[None; 32]; 32];
+    let mut max_bias_diff: f64 = 0.0;
+    let mut max_tree: f64 = 0.0;
+    let mut max_bic: f64 = 0.0;
     for bit_in in 0..32 {
         for bit_out in 0..32 {
-            let color_avalanche =
-                (stats.avalanche[bit_in][bit_out].min(1.0).max(0.0) * 255.0) as u8;
-            let color_avalanche_bias =
-                (stats.avalanche_avg_bias[bit_in][bit_out].min(1.0).max(0.0) * 255.0) as u8;
-            let color_tree = (stats.tree_bias[bit_in][bit_out].min(1.0).max(0.0) * 255.0) as u8;
-            plot(bit_out, bit_in, color_avalanche);
-            plot(bit_out + 32, bit_in, color_avalanche_bias);
-            plot(bit_out + 64, bit_in, color_tree);
+            avalanche[bit_in][bit_out] = Some(stats.avalanche[bit_in][bit_out] - 0.5);
+            if bit_in < bit_out {
+                let diff = stats.avalanche_avg_bias[bit_in][bit_out] - TARGET_BIAS[bit_out];
+                bias[bit_in][bit_out] = Some(diff);
+                max_bias_diff = max_bias_diff.max(diff.abs());
+
+                let corr = stats.bit_independence[bit_in][bit_out];
+                bic[bit_in][bit_out] = Some(corr);
+                max_bic = max_bic.max(corr.abs());
+
+                if bit_out < 31 {
+                    two_bit[bit_in][bit_out] = Some(stats.two_bit_avalanche[bit_in][bit_out] - 0.5);
+                }
+            }
+            tree[bit_in][bit_out] = Some(stats.tree_bias[bit_in][bit_out]);
+            max_tree = max_tree.max(stats.tree_bias[bit_in][bit_out]);
+            seed[bit_in][bit_out] = Some(stats.seed_avalanche[bit_in][bit_out] - 0.5);
+        }
+    }
+    let max_bias_diff = max_bias_diff.max(0.001);
+    let max_tree = max_tree.max(1.0);
+    let max_bic = max_bic.max(0.01);
+
+    let mut entropy = [None; 32];
+    let mut max_entropy_loss: f64 = 0.0;
+    for (bit_out, e) in entropy.iter_mut().enumerate() {
+        let loss = 1.0 - stats.output_entropy[bit_out];
+        *e = Some(loss);
+        max_entropy_loss = max_entropy_loss.max(loss);
+    }
+    let max_entropy_loss = max_entropy_loss.max(0.001);
+
+    let in_out = ("in", "out");
+    draw_heatmap(
+        canvas,
+        (0, 0),
+        "Avalanche - 0.5",
+        in_out,
+        &avalanche,
+        (-0.5, 0.5),
+        true,
+    );
+    draw_heatmap(
+        canvas,
+        (PANEL_WIDTH, 0),
+        "Avg bias - target",
+        in_out,
+        &bias,
+        (-max_bias_diff, max_bias_diff),
+        true,
+    );
+    draw_heatmap(
+        canvas,
+        (PANEL_WIDTH * 2, 0),
+        "Tree bias",
+        in_out,
+        &tree,
+        (0.0, max_tree),
+        false,
+    );
+    draw_heatmap(
+        canvas,
+        (0, PANEL_HEIGHT),
+        "Seed avalanche - 0.5",
+        ("seed", "out"),
+        &seed,
+        (-0.5, 0.5),
+        true,
+    );
+    draw_heatmap(
+        canvas,
+        (PANEL_WIDTH, PANEL_HEIGHT),
+        "Bit independence",
+        ("out", "out"),
+        &bic,
+        (-max_bic, max_bic),
+        true,
+    );
+    draw_heatmap(
+        canvas,
+        (PANEL_WIDTH * 2, PANEL_HEIGHT),
+        "2-bit avalanche - 0.5",
+        ("in", "in"),
+        &two_bit,
+        (-0.5, 0.5),
+        true,
+    );
+    draw_strip(
+        canvas,
+        (0, PANEL_HEIGHT * PANEL_ROWS),
+        "1 - output entropy",
+        "out",
+        &entropy,
+        (0.0, max_entropy_loss),
+        false,
+    );
+}
+
+/// Draws a single titled heatmap panel, with axis ticks and a colorbar
+/// legend.
+///
+/// `values` is indexed as `[y][x]`, with `None` and NaN drawn in gray, and
+/// `axes` names the kind of bit on the y and x axes (e.g. "in" and "out").
+/// Values are mapped linearly from `range` onto the colormap, which is
+/// diverging if `diverging` is true and sequential otherwise.
+fn draw_heatmap<C: Canvas>(
+    canvas: &mut C,
+    (panel_x, panel_y): (usize, usize),
+    title: &str,
+    (y_name, x_name): (&str, &str),
+    values: &[[Option<f64>; 32]; 32],
+    range: (f64, f64),
+    diverging: bool,
+) {
+    let color = |v: f64| legend_color(v, range, diverging);
+    let map_x = panel_x + PANEL_LEFT;
+    let map_y = panel_y + PANEL_TOP;
+
+    // Title.
+    canvas.text(map_x, panel_y + 6, 2, title, TEXT_COLOR);
+
+    // Cells.  Input bit zero is at the bottom.
+    for (bit_in, row) in values.iter().enumerate() {
+        for (bit_out, value) in row.iter().enumerate() {
+            canvas.rect(
+                map_x + bit_out * CELL_SIZE,
+                map_y + (31 - bit_in) * CELL_SIZE,
+                CELL_SIZE,
+                CELL_SIZE,
+                value.filter(|v| !v.is_nan()).map(color).unwrap_or(NA_COLOR),
+            );
+        }
+    }
+
+    // X axis (output bit).
+    draw_x_axis(canvas, (map_x, map_y + MAP_SIZE), x_name);
+
+    // Y axis (input bit).
+    for &bit in &[0, 8, 16, 24, 31] {
+        let label = format!("{}", bit);
+        let label_width = text_width(&label, 1);
+        let center = bit * CELL_SIZE + CELL_SIZE / 2;
+        let y = map_y + MAP_SIZE - center - 1;
+        canvas.rect(map_x - 3, y, 3, 1, TEXT_COLOR);
+        canvas.text(
+            map_x - 5 - label_width,
+            y - TEXT_HEIGHT / 2,
+            1,
+            &label,
+            TEXT_COLOR,
+        );
+    }
+    canvas.text(panel_x + 4, map_y + MAP_SIZE / 2 - 8, 1, y_name, TEXT_COLOR);
+    canvas.text(panel_x + 4, map_y + MAP_SIZE / 2 + 2, 1, "bit", TEXT_COLOR);
+
+    // Colorbar legend.
+    draw_legend(canvas, (panel_x, map_y + MAP_SIZE + 34), range, diverging);
+}
+
+/// Draws a single titled strip panel: one row of cells, with the same x axis
+/// and legend as `draw_heatmap()`.
+fn draw_strip<C: Canvas>(
+    canvas: &mut C,
+    (panel_x, panel_y): (usize, usize),
+    title: &str,
+    x_name: &str,
+    values: &[Option<f64>; 32],
+    range: (f64, f64),
+    diverging: bool,
+) {
+    let map_x = panel_x + PANEL_LEFT;
+    let map_y = panel_y + PANEL_TOP;
+
+    canvas.text(map_x, panel_y + 6, 2, title, TEXT_COLOR);
+    for (bit_out, value) in values.iter().enumerate() {
+        canvas.rect(
+            map_x + bit_out * CELL_SIZE,
+            map_y,
+            CELL_SIZE,
+            STRIP_SIZE,
+            value
+                .filter(|v| !v.is_nan())
+                .map(|v| legend_color(v, range, diverging))
+                .unwrap_or(NA_COLOR),
+        );
+    }
+    draw_x_axis(canvas, (map_x, map_y + STRIP_SIZE), x_name);
+    draw_legend(canvas, (panel_x, map_y + STRIP_SIZE + 34), range, diverging);
+}
+
+/// The color of `v` in a panel whose values are mapped linearly from `range`
+/// onto a diverging or sequential colormap.
+fn legend_color(v: f64, range: (f64, f64), diverging: bool) -> [u8; 3] {
+    let t = (v - range.0) / (range.1 - range.0);
+    if diverging {
+        colormap_diverging(t * 2.0 - 1.0)
+    } else {
+        colormap_viridis(t)
+    }
+}
+
+/// Draws the bit ticks and label of a panel's x axis, just below `y`.
+fn draw_x_axis<C: Canvas>(canvas: &mut C, (map_x, y): (usize, usize), x_name: &str) {
+    for &bit in &[0, 8, 16, 24, 31] {
+        let label = format!("{}", bit);
+        let center = bit * CELL_SIZE + CELL_SIZE / 2;
+        canvas.rect(map_x + center, y, 1, 3, TEXT_COLOR);
+        canvas.text(
+            map_x + center - text_width(&label, 1) / 2,
+            y + 5,
+            1,
+            &label,
+            TEXT_COLOR,
+        );
+    }
+    let x_label = &format!("{} bit", x_name);
+    canvas.text(
+        map_x + (MAP_SIZE - text_width(x_label, 1)) / 2,
+        y + 16,
+        1,
+        x_label,
+        TEXT_COLOR,
+    );
+}
+
+/// Draws a panel's colorbar legend at `bar_y`, with labelled ends and middle.
+fn draw_legend<C: Canvas>(
+    canvas: &mut C,
+    (panel_x, bar_y): (usize, usize),
+    range: (f64, f64),
+    diverging: bool,
+) {
+    let map_x = panel_x + PANEL_LEFT;
+    for i in 0..MAP_SIZE {
+        let v = range.0 + (range.1 - range.0) * (i as f64 + 0.5) / MAP_SIZE as f64;
+        canvas.rect(map_x + i, bar_y, 1, 10, legend_color(v, range, diverging));
+    }
+    for &(frac, v) in &[
+        (0.0, range.0),
+        (0.5, (range.0 + range.1) * 0.5),
+        (1.0, range.1),
+    ] {
+        let label = if diverging {
+            format!("{:+.3}", v)
+        } else {
+            format!("{:.3}", v)
+        };
+        let label = if diverging && v == 0.0 {
+            "0".to_string()
+        } else {
+            label
+        };
+        let x = map_x + ((MAP_SIZE - 1) as f64 * frac) as usize;
+        let label_x = (x + 1)
+            .saturating_sub((text_width(&label, 1) as f64 * frac) as usize)
+            .max(panel_x);
+        canvas.rect(x, bar_y + 10, 1, 3, TEXT_COLOR);
+        canvas.text(label_x, bar_y + 15, 1, &label, TEXT_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds enough words to flush the planes several times, checking the
+    /// counts both mid-stream and at the end.
+    #[test]
+    fn bit_counter_matches_naive_count() {
+        let mut counter = BitCounter::new();
+        let mut naive = [0u64; 32];
+        for i in 0..1000 {
+            let word = match i {
+                0 => 0,
+                1 => !0,
+                _ => hash_u32(i, 0x9e3779b9),
+            };
+            counter.add(word);
+            for (bit, n) in naive.iter_mut().enumerate() {
+                *n += ((word >> bit) & 1) as u64;
+            }
+            if i == 300 || i == 999 {
+                assert_eq!(counter.counts(), &naive, "after {} words", i + 1);
+            }
+        }
+
+        // All bits set on every word fills the planes as fast as possible.
+        let mut counter = BitCounter::new();
+        for _ in 0..600 {
+            counter.add(!0);
+        }
+        assert_eq!(counter.counts(), &[600; 32]);
+    }
+
+    #[test]
+    fn seed_source_names() {
+        for &source in SeedSource::ALL {
+            assert_eq!(source.name().parse::<SeedSource>(), Ok(source));
+        }
+        assert!("Random".parse::<SeedSource>().is_err());
+        assert!("".parse::<SeedSource>().is_err());
+    }
+
+    #[test]
+    fn seed_source_seeds() {
+        let base = 0xfffffff0;
+        for n in 0..1000 {
+            assert_eq!(SeedSource::Sequential.seed(n, base), base.wrapping_add(n));
+            assert_eq!(SeedSource::HashCombine.seed(n, base), hash_combine(base, n));
+            let gray = SeedSource::GrayCode.seed(n, base) ^ SeedSource::GrayCode.seed(n + 1, base);
+            assert_eq!(gray.count_ones(), 1, "{}", n);
+        }
+
+        // Every seed with at most two bits set, once per period.
+        let period = 1 + 32 + 32 * 31 / 2;
+        let mut low: Vec<u32> = (0..period)
+            .map(|n| SeedSource::LowPopcount.seed(n, base))
+            .collect();
+        assert!(low.iter().all(|s| s.count_ones() <= 2));
+        assert_eq!(SeedSource::LowPopcount.seed(period + 5, base), low[5]);
+        low.sort_unstable();
+        low.dedup();
+        assert_eq!(low.len() as u32, period);
+    }
+
+    #[test]
+    fn score_config_parsing() {
+        let config: ScoreConfig = "tree=0.5, points=1024,seeds=8".parse().unwrap();
+        assert_eq!(config.tree_bias, 0.5);
+        assert_eq!(config.avalanche, 1.0);
+        assert_eq!((config.points, config.seeds), (1024, 8));
+
+        for bad in &[
+            "points=-5",
+            "seeds=2.7",
+            "points=",
+            "tree=x",
+            "tree",
+            "foo=1",
+        ] {
+            assert!(bad.parse::<ScoreConfig>().is_err(), "{}", bad);
         }
     }
-    png_encode_mini::write_rgba_from_u8(file, &image, WIDTH as u32, HEIGHT as u32);
 }